@@ -0,0 +1,49 @@
+#[cfg(any(test, feature = "test-util"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of wall-clock time, in fractional seconds since the Unix epoch.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> f64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64()
+    }
+}
+
+/// A clock that only moves when told to, for tests.
+#[cfg(any(test, feature = "test-util"))]
+pub struct ManualClock {
+    bits: AtomicU64,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ManualClock {
+    pub fn new(start: f64) -> Self {
+        Self {
+            bits: AtomicU64::new(start.to_bits()),
+        }
+    }
+
+    pub fn set(&self, now: f64) {
+        self.bits.store(now.to_bits(), Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: f64) {
+        self.set(self.now() + secs);
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        f64::from_bits(self.bits.load(Ordering::SeqCst))
+    }
+}
@@ -27,7 +27,7 @@ fn default_editor() -> String {
     "swappy -f".into()
 }
 fn default_actions() -> Vec<String> {
-    vec!["drag".into(), "open".into(), "edit".into(), "copy".into()]
+    ["drag", "open", "edit", "copy", "rename", "move", "trash"].map(String::from).to_vec()
 }
 fn default_menu_dismiss_seconds() -> u64 {
     8
@@ -35,6 +35,24 @@ fn default_menu_dismiss_seconds() -> u64 {
 fn default_drag_command() -> String {
     "builtin".into()
 }
+fn default_picker() -> String {
+    "auto".into()
+}
+fn default_startup_scan_seconds() -> u64 {
+    300
+}
+fn default_batch_window_ms() -> u64 {
+    1500
+}
+fn default_text_preview_lines() -> usize {
+    12
+}
+fn default_text_preview_bytes() -> u64 {
+    64 * 1024
+}
+fn default_status_format() -> String {
+    " {name}{count}".into()
+}
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct MenuStyle {
@@ -74,6 +92,128 @@ impl Default for MenuStyle {
     }
 }
 
+/// Keyboard shortcuts in the dropdown menu, as GDK key names (`"j"`,
+/// `"Down"`, `"Return"`, `"Delete"`). Number keys always jump to that index.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MenuKeys {
+    /// Move to the next older entry
+    #[serde(default = "MenuKeys::default_older")]
+    pub older: Vec<String>,
+    /// Move to the next newer entry
+    #[serde(default = "MenuKeys::default_newer")]
+    pub newer: Vec<String>,
+    #[serde(default = "MenuKeys::default_open")]
+    pub open: Vec<String>,
+    #[serde(default = "MenuKeys::default_copy")]
+    pub copy: Vec<String>,
+    #[serde(default = "MenuKeys::default_edit")]
+    pub edit: Vec<String>,
+    #[serde(default = "MenuKeys::default_drag")]
+    pub drag: Vec<String>,
+    #[serde(default = "MenuKeys::default_pin")]
+    pub pin: Vec<String>,
+    #[serde(default = "MenuKeys::default_rename")]
+    pub rename: Vec<String>,
+    /// Open the "Move to…" list
+    #[serde(default = "MenuKeys::default_move_to", rename = "move")]
+    pub move_to: Vec<String>,
+    #[serde(default = "MenuKeys::default_trash")]
+    pub trash: Vec<String>,
+    /// Permanently delete (still asks first); unbound by default
+    #[serde(default)]
+    pub delete: Vec<String>,
+    /// Bring back the last trashed entry
+    #[serde(default = "MenuKeys::default_undo")]
+    pub undo: Vec<String>,
+    #[serde(default = "MenuKeys::default_close")]
+    pub close: Vec<String>,
+}
+
+impl MenuKeys {
+    fn keys(names: &[&str]) -> Vec<String> { names.iter().map(|s| s.to_string()).collect() }
+    fn default_older() -> Vec<String> { Self::keys(&["Down", "j"]) }
+    fn default_newer() -> Vec<String> { Self::keys(&["Up", "k"]) }
+    fn default_open() -> Vec<String> { Self::keys(&["Return"]) }
+    fn default_copy() -> Vec<String> { Self::keys(&["c"]) }
+    fn default_edit() -> Vec<String> { Self::keys(&["e"]) }
+    fn default_drag() -> Vec<String> { Self::keys(&["d"]) }
+    fn default_pin() -> Vec<String> { Self::keys(&["p"]) }
+    fn default_rename() -> Vec<String> { Self::keys(&["F2"]) }
+    fn default_move_to() -> Vec<String> { Self::keys(&["m"]) }
+    fn default_trash() -> Vec<String> { Self::keys(&["Delete"]) }
+    fn default_undo() -> Vec<String> { Self::keys(&["u"]) }
+    fn default_close() -> Vec<String> { Self::keys(&["Escape", "q"]) }
+}
+
+impl Default for MenuKeys {
+    fn default() -> Self {
+        Self {
+            older: Self::default_older(),
+            newer: Self::default_newer(),
+            open: Self::default_open(),
+            copy: Self::default_copy(),
+            edit: Self::default_edit(),
+            drag: Self::default_drag(),
+            pin: Self::default_pin(),
+            rename: Self::default_rename(),
+            move_to: Self::default_move_to(),
+            trash: Self::default_trash(),
+            delete: Vec::new(),
+            undo: Self::default_undo(),
+            close: Self::default_close(),
+        }
+    }
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+/// A `[[rules]]` entry: what to do with a new file that matches. Every
+/// condition given must hold; the first rule that matches is applied.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// Shown in logs and `glance rules test`
+    #[serde(default)]
+    pub name: String,
+
+    /// The file arrived in this directory
+    pub dir: Option<String>,
+    /// Its name matches this glob, e.g. `"invoice*.pdf"`
+    pub glob: Option<String>,
+    /// Its MIME type, e.g. `"application/pdf"` or `"image/*"`
+    pub mime: Option<String>,
+    /// Size bounds in bytes
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Age bounds in days, going by the file's modification time. A rule
+    /// with `min_age_days` is also tried on files already in history, on
+    /// the watcher's hourly sweep
+    pub min_age_days: Option<f64>,
+    pub max_age_days: Option<f64>,
+
+    /// New name; `{name}`, `{stem}`, `{ext}` and `{date}` (the file's
+    /// modification date) are filled in
+    pub rename: Option<String>,
+    /// Put a copy in this directory
+    pub copy: Option<String>,
+    /// Move the file to this directory
+    #[serde(rename = "move")]
+    pub move_to: Option<String>,
+    /// Move it to the trash instead of announcing it
+    #[serde(default)]
+    pub trash: bool,
+    /// Run this command with the final path appended, once the other
+    /// actions are done (a trashed file's path in the trash)
+    pub run: Option<String>,
+    /// Labels for the history entry
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Show the file in the widget (false: act on it quietly)
+    #[serde(default = "default_notify")]
+    pub notify: bool,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     #[serde(default = "default_watch_dirs")]
@@ -96,8 +236,41 @@ pub struct Config {
     pub menu_dismiss_seconds: u64,
     #[serde(default = "default_drag_command")]
     pub drag_command: String,
+    #[serde(default = "default_picker")]
+    pub picker: String,
+    /// Offered by "Move to…" ahead of recent destinations
+    #[serde(default)]
+    pub favorite_dirs: Vec<String>,
+    #[serde(default = "default_status_format")]
+    pub status_format: String,
+    #[serde(default = "default_batch_window_ms")]
+    pub batch_window_ms: u64,
+    #[serde(default)]
+    pub startup_scan: bool,
+    #[serde(default = "default_startup_scan_seconds")]
+    pub startup_scan_seconds: u64,
+    /// Lines of a text file shown in the menu (0 = just an icon)
+    #[serde(default = "default_text_preview_lines")]
+    pub text_preview_lines: usize,
+    /// Read at most this much of a file for its preview
+    #[serde(default = "default_text_preview_bytes")]
+    pub text_preview_bytes: u64,
     #[serde(default)]
     pub menu_style: MenuStyle,
+    #[serde(default)]
+    pub menu_keys: MenuKeys,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Where state, lock and pid files live (`$XDG_RUNTIME_DIR`).
+    #[serde(skip, default = "runtime_dir")]
+    pub runtime_dir: PathBuf,
+    /// Where what should outlive a reboot lives (`$XDG_STATE_HOME/glance`).
+    #[serde(skip, default = "state_dir")]
+    pub state_dir: PathBuf,
+    /// Where the shared thumbnail cache and glance's own previews live
+    /// (`$XDG_CACHE_HOME`).
+    #[serde(skip, default = "cache_dir")]
+    pub cache_dir: PathBuf,
 }
 
 impl Default for Config {
@@ -113,7 +286,20 @@ impl Default for Config {
             actions: default_actions(),
             menu_dismiss_seconds: default_menu_dismiss_seconds(),
             drag_command: default_drag_command(),
+            picker: default_picker(),
+            favorite_dirs: Vec::new(),
+            status_format: default_status_format(),
+            batch_window_ms: default_batch_window_ms(),
+            startup_scan: false,
+            startup_scan_seconds: default_startup_scan_seconds(),
+            text_preview_lines: default_text_preview_lines(),
+            text_preview_bytes: default_text_preview_bytes(),
             menu_style: MenuStyle::default(),
+            menu_keys: MenuKeys::default(),
+            rules: Vec::new(),
+            runtime_dir: runtime_dir(),
+            state_dir: state_dir(),
+            cache_dir: cache_dir(),
         }
     }
 }
@@ -131,6 +317,11 @@ impl Config {
             .into_iter()
             .map(|d| shellexpand::tilde(&d).into_owned())
             .collect();
+        cfg.favorite_dirs = cfg
+            .favorite_dirs
+            .into_iter()
+            .map(|d| shellexpand::tilde(&d).into_owned())
+            .collect();
         Ok(cfg)
     }
 
@@ -138,12 +329,36 @@ impl Config {
         self.actions.iter().any(|a| a == name)
     }
 
-    pub fn state_file() -> PathBuf {
-        runtime_dir().join("glance-latest.json")
+    pub fn state_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance-latest.json")
+    }
+
+    pub fn pid_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance.pid")
+    }
+
+    pub fn menu_lock_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance-menu.lock")
+    }
+
+    pub fn menu_pid_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance-menu.pid")
+    }
+
+    pub fn menu_pos_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance-menu-pos")
     }
 
-    pub fn pid_file() -> PathBuf {
-        runtime_dir().join("glance.pid")
+    pub fn trash_undo_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance-trash-undo.json")
+    }
+
+    pub fn expected_file(&self) -> PathBuf {
+        self.runtime_dir.join("glance-expected.json")
+    }
+
+    pub fn recent_dirs_file(&self) -> PathBuf {
+        self.state_dir.join("recent-dirs.json")
     }
 }
 
@@ -153,6 +368,24 @@ fn runtime_dir() -> PathBuf {
     )
 }
 
+fn state_dir() -> PathBuf {
+    let base = std::env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| "/tmp".into()))
+                .join(".local/state")
+        });
+    base.join("glance")
+}
+
+fn cache_dir() -> PathBuf {
+    std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| "/tmp".into())).join(".cache")
+        })
+}
+
 fn config_path() -> PathBuf {
     let base = std::env::var("XDG_CONFIG_HOME")
         .map(PathBuf::from)
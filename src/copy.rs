@@ -1,16 +1,28 @@
+use crate::clock::{Clock, SystemClock};
 use crate::config::Config;
+use crate::runner;
+use crate::select::Selector;
 use crate::state::read_history;
 use anyhow::Result;
+use std::path::Path;
 use std::process::Command;
 
-pub fn run(cfg: &Config) -> Result<()> {
-    let history = read_history(&Config::state_file());
-    let manually_scrolled = history.selected != 0;
-    if let Some(st) = history.current().filter(|e| manually_scrolled || !e.is_expired(cfg.dismiss_seconds)) {
-        if st.path.exists() {
-            let _ = Command::new("wl-copy")
-                .arg(st.path.to_string_lossy().as_ref())
-                .spawn();
+/// The paths that still exist, one per line, as handed to `wl-copy`.
+pub fn path_list(paths: &[&Path]) -> String {
+    paths
+        .iter()
+        .filter(|p| p.exists())
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn run(cfg: &Config, sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    if let Some(st) = sel.target(&history, SystemClock.now(), cfg)? {
+        let text = path_list(&st.paths());
+        if !text.is_empty() {
+            runner::launch(Command::new("wl-copy").arg(text));
         }
     }
     Ok(())
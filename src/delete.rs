@@ -0,0 +1,67 @@
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::log;
+use crate::select::Selector;
+use crate::state::{read_history, update_history, with_history, FileState};
+use crate::util::{existing_paths, signal_waybar};
+use anyhow::{Context, Result};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Permanently delete an entry's files and take it out of history.
+/// Returns how many files went.
+pub fn delete_entry(cfg: &Config, entry: &FileState) -> Result<usize> {
+    let paths = existing_paths(&entry.paths());
+    let mut deleted = 0;
+    for p in &paths {
+        if let Err(e) = std::fs::remove_file(p) {
+            // what did go is gone from history too
+            if deleted > 0 {
+                update_history(&cfg.state_file(), |h| {
+                    let mut changed = false;
+                    for gone in &paths[..deleted] {
+                        changed |= h.mark_missing(gone);
+                    }
+                    changed
+                })?;
+                signal_waybar(cfg.signal_number);
+            }
+            return Err(e).with_context(|| format!("can't delete {}", p.display()));
+        }
+        deleted += 1;
+    }
+    with_history(&cfg.state_file(), |h| {
+        h.remove(entry);
+    })?;
+    signal_waybar(cfg.signal_number);
+    log::info!("deleted"; path = entry.path.display(), count = deleted);
+    Ok(deleted)
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("refusing to delete without confirmation; pass --yes");
+    }
+    eprint!("{question} [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+pub fn run(cfg: &Config, sel: &Selector, yes: bool) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let Some(entry) = sel.target(&history, SystemClock.now(), cfg)? else {
+        return Ok(());
+    };
+    let count = existing_paths(&entry.paths()).len();
+    let what = match count {
+        1 => entry.name.clone(),
+        n => format!("{n} files ({})", entry.name),
+    };
+    if !yes && !confirm(&format!("Permanently delete {what}?"))? {
+        return Ok(());
+    }
+    delete_entry(cfg, entry)?;
+    println!("deleted {what}");
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use crate::config::Config;
+use crate::state::with_history;
+use crate::util::signal_waybar;
+use anyhow::Result;
+
+pub fn run(cfg: &Config) -> Result<()> {
+    with_history(&cfg.state_file(), |history| history.dismiss())?;
+    signal_waybar(cfg.signal_number);
+    Ok(())
+}
@@ -1,45 +1,57 @@
+use crate::clock::{Clock, SystemClock};
 use crate::config::Config;
+use crate::copy::path_list;
+use crate::log;
+use crate::runner;
+use crate::select::Selector;
 use crate::state::read_history;
-use crate::util::{cursor_pos, find_monitor_at};
+use crate::util::{cursor_pos, existing_paths, find_monitor_at};
 use anyhow::Result;
 use gtk4::gdk;
 use gtk4::gio;
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4_layer_shell::{Edge, Layer, LayerShell};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Duration;
 
 const OVERLAY_W: i32 = 200;
+/// How long an external drag tool may stay up before it's killed, so a
+/// stuck one doesn't block forever.
+const EXTERNAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `text/uri-list` payload for the given files.
+pub fn uri_list(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| format!("{}\r\n", gio::File::for_path(p).uri()))
+        .collect()
+}
+
+/// What a drag offers the drop target: a `text/uri-list` for file managers
+/// and browsers, plus the bare paths as text for terminals and editors.
+pub fn content_provider(paths: &[PathBuf]) -> gdk::ContentProvider {
+    let uris = glib::Bytes::from_owned(uri_list(paths).into_bytes());
+    let text = path_list(&paths.iter().map(PathBuf::as_path).collect::<Vec<&Path>>());
+    let text = glib::Bytes::from_owned(text.into_bytes());
+    gdk::ContentProvider::new_union(&[
+        gdk::ContentProvider::for_bytes("text/uri-list", &uris),
+        gdk::ContentProvider::for_bytes("text/plain;charset=utf-8", &text),
+        gdk::ContentProvider::for_bytes("text/plain", &text),
+    ])
+}
 
-fn run_external(cmd: &str, path: &std::path::Path) -> Result<()> {
+fn run_external(cmd: &str, paths: &[PathBuf]) -> Result<()> {
     let mut parts = cmd.split_whitespace();
     let bin = parts.next().unwrap_or("ripdrag");
-    let args: Vec<&str> = parts.collect();
-    let mut child = Command::new(bin)
-        .args(&args)
-        .arg(path)
-        .spawn()?;
-    // Wait with a timeout instead of blocking indefinitely
-    let timeout = Duration::from_secs(30);
-    let start = std::time::Instant::now();
-    loop {
-        match child.try_wait() {
-            Ok(Some(_)) => break,
-            Ok(None) => {
-                if start.elapsed() >= timeout {
-                    let _ = child.kill();
-                    break;
-                }
-                std::thread::sleep(Duration::from_millis(100));
-            }
-            Err(_) => break,
-        }
+    if runner::run_for(Command::new(bin).args(parts).args(paths), EXTERNAL_TIMEOUT)? {
+        log::warn!("stopped {bin} after {}s", EXTERNAL_TIMEOUT.as_secs());
     }
     Ok(())
 }
 
-fn run_builtin(cfg: &Config, filepath: std::path::PathBuf) -> Result<()> {
+fn run_builtin(cfg: &Config, paths: Vec<PathBuf>) -> Result<()> {
     let (cursor_x, cursor_y) = cursor_pos().unwrap_or((800, 0));
     let monitor_info = find_monitor_at(cursor_x, cursor_y);
     let bar_height = cfg.bar_height;
@@ -95,15 +107,8 @@ fn run_builtin(cfg: &Config, filepath: std::path::PathBuf) -> Result<()> {
         let ds = gtk4::DragSource::new();
         ds.set_actions(gdk::DragAction::COPY);
 
-        let file = gio::File::for_path(&filepath);
-        let uri = format!("{}\r\n", file.uri());
-
-        ds.connect_prepare(move |_, _, _| {
-            Some(gdk::ContentProvider::for_bytes(
-                "text/uri-list",
-                &glib::Bytes::from(uri.as_bytes()),
-            ))
-        });
+        let paths = paths.clone();
+        ds.connect_prepare(move |_, _, _| Some(content_provider(&paths)));
 
         let app_ref = app.clone();
         ds.connect_drag_end(move |_, _, _| {
@@ -139,18 +144,31 @@ fn run_builtin(cfg: &Config, filepath: std::path::PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn run(cfg: &Config) -> Result<()> {
-    let history = read_history(&Config::state_file());
-    let Some(st) = history.current().filter(|e| !e.is_expired(cfg.dismiss_seconds)) else {
-        return Ok(());
+/// Drag the selected entry (by default the one the widget shows), or with
+/// `all`/`indices` several history entries at once.
+pub fn run(cfg: &Config, all: bool, indices: &[usize], sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let picked = if all {
+        history.paths_at(&(0..history.entries.len()).collect::<Vec<_>>())
+    } else if !indices.is_empty() {
+        if let Some(i) = indices.iter().find(|&&i| i >= history.entries.len()) {
+            anyhow::bail!("no history entry matches --indices {i}");
+        }
+        history.paths_at(indices)
+    } else {
+        match sel.target(&history, SystemClock.now(), cfg)? {
+            Some(st) => st.paths(),
+            None => return Ok(()),
+        }
     };
-    if !st.path.exists() {
+    let paths = existing_paths(&picked);
+    if paths.is_empty() {
         return Ok(());
     }
 
     if cfg.drag_command == "builtin" {
-        run_builtin(cfg, st.path.clone())
+        run_builtin(cfg, paths)
     } else {
-        run_external(&cfg.drag_command, &st.path)
+        run_external(&cfg.drag_command, &paths)
     }
 }
@@ -0,0 +1,53 @@
+use crate::config::Config;
+use crate::select::Selector;
+use crate::state::{read_history, FileState};
+use crate::util::print_stdout;
+use anyhow::Result;
+use clap::ValueEnum;
+use gtk4::gio;
+use gtk4::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Field {
+    Path,
+    Name,
+    Size,
+    Uri,
+}
+
+/// The requested field as printed. `path` and `uri` give one line per file,
+/// so a batch entry lists all of its members.
+fn field(st: &FileState, field: Field) -> String {
+    match field {
+        Field::Path => st
+            .paths()
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Field::Name => st.name.clone(),
+        Field::Size => st.size.to_string(),
+        Field::Uri => st
+            .paths()
+            .iter()
+            .map(|p| gio::File::for_path(p).uri().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Print one field of a history entry: the one picked by `index` or the
+/// selector, else the selected one.
+pub fn run(cfg: &Config, index: Option<usize>, sel: &Selector, which: Field) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let picked = match index {
+        Some(i) => Selector::index(i).pick(&history)?,
+        None => sel.pick(&history)?,
+    };
+    let i = picked.unwrap_or(history.selected);
+    let Some(st) = history.entries.get(i) else {
+        anyhow::bail!("no history entry at index {i}");
+    };
+    print_stdout(&format!("{}\n", field(st, which)))?;
+    Ok(())
+}
@@ -1,6 +1,8 @@
+use crate::runner;
 use anyhow::Result;
 use std::fs;
 use std::path::PathBuf;
+use std::process::Command;
 
 const CONFIG_TOML: &str = r#"# glance daemon configuration
 
@@ -65,6 +67,28 @@ const WAYBAR_CSS: &str = r#"
 }
 "#;
 
+fn systemd_unit() -> String {
+    let bin = glance_bin();
+    format!(
+        r#"[Unit]
+Description=glance file preview watcher
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={bin} watch --replace
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+WatchdogSec=30
+
+[Install]
+WantedBy=graphical-session.target
+"#
+    )
+}
+
 fn config_base() -> PathBuf {
     std::env::var("XDG_CONFIG_HOME")
         .map(PathBuf::from)
@@ -204,7 +228,9 @@ fn setup_waybar_css() -> Result<()> {
     Ok(())
 }
 
-fn setup_hyprland() -> Result<()> {
+/// Add the SUPER+V keybind, and unless systemd starts the watcher, the
+/// `exec-once` autostart.
+fn setup_hyprland(systemd: bool) -> Result<()> {
     let path = config_base().join("hypr/hyprland.conf");
     if !path.exists() {
         skip("hyprland.conf not found, skipping autostart setup");
@@ -216,19 +242,57 @@ fn setup_hyprland() -> Result<()> {
     }
     let mut content = fs::read_to_string(&path)?;
     let bin = glance_bin();
-    content.push_str(&format!("\nexec-once = {bin} watch\n"));
+    if !systemd {
+        content.push_str(&format!("\nexec-once = {bin} watch\n"));
+    }
     content.push_str(&format!("bind = SUPER, V, exec, {bin} drag\n"));
     fs::write(&path, content)?;
-    ok("added exec-once and SUPER+V keybind");
+    if systemd {
+        ok("added SUPER+V keybind");
+    } else {
+        ok("added exec-once and SUPER+V keybind");
+    }
     Ok(())
 }
 
-pub fn run() -> Result<()> {
+fn systemctl(args: &[&str]) -> Result<()> {
+    let out = runner::output(Command::new("systemctl").arg("--user").args(args))?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn setup_systemd() -> Result<()> {
+    let path = config_base().join("systemd/user/glance.service");
+    if path.exists() {
+        skip(&format!("unit already exists: {}", path.display()));
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, systemd_unit())?;
+        ok(&format!("created {}", path.display()));
+    }
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", "glance.service"])?;
+    ok("enabled and started glance.service");
+    Ok(())
+}
+
+pub fn run(systemd: bool) -> Result<()> {
     eprintln!("\n  glance init\n");
     setup_config()?;
     setup_waybar_module()?;
     setup_waybar_css()?;
-    setup_hyprland()?;
+    setup_hyprland(systemd)?;
+    if systemd {
+        setup_systemd()?;
+    }
     eprintln!("\n  Done! Restart Waybar to activate: pkill waybar && waybar &\n");
     Ok(())
 }
@@ -0,0 +1,170 @@
+use crate::config::Config;
+use anyhow::Result;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+/// How long a starting watcher waits on the pid lock before deciding another
+/// instance really holds it (covers a `--status` probe or a dying instance).
+const LOCK_GRACE: Duration = Duration::from_millis(200);
+/// How long `--replace` and `glance stop` wait for the old watcher to exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The watcher's claim on `glance.pid`: an exclusive flock held for as long
+/// as the process lives. The kernel drops it on exit, so a crashed watcher
+/// never leaves a stale lock behind.
+pub struct PidLock {
+    _file: File,
+}
+
+impl PidLock {
+    /// Become the one running watcher. If another is alive, fail, or with
+    /// `replace` ask it to exit and take over.
+    pub fn acquire(cfg: &Config, replace: bool) -> Result<Self> {
+        let pid_file = cfg.pid_file();
+        let mut file = File::options()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&pid_file)?;
+
+        let mut locked = try_lock(&file, LOCK_GRACE);
+        if !locked || legacy_watcher(&mut file).is_some() {
+            let pid = read_pid(&mut file);
+            if !replace {
+                match pid {
+                    Some(pid) => anyhow::bail!(
+                        "glance watch is already running (pid {pid}); \
+                         use --replace or `glance stop`"
+                    ),
+                    None => anyhow::bail!("glance watch is already running"),
+                }
+            }
+            if let Some(pid) = pid {
+                terminate(pid);
+            }
+            locked = locked || try_lock(&file, STOP_TIMEOUT);
+            if !locked {
+                anyhow::bail!("timed out waiting for the running watcher to exit");
+            }
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        Ok(Self { _file: file })
+    }
+}
+
+fn try_lock(file: &File, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret == 0 {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<i32> {
+    let mut s = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut s).ok()?;
+    s.trim().parse().ok().filter(|&pid| pid > 0)
+}
+
+fn is_alive(pid: i32) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+/// A watcher from before the pid file was locked: alive, named glance, but
+/// not holding the lock we just got.
+fn legacy_watcher(file: &mut File) -> Option<i32> {
+    let pid = read_pid(file)?;
+    if pid == std::process::id() as i32 || !is_alive(pid) {
+        return None;
+    }
+    let comm = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    (comm.trim() == "glance").then_some(pid)
+}
+
+/// SIGTERM the process and wait for it to go away.
+fn terminate(pid: i32) -> bool {
+    unsafe { libc::kill(pid, libc::SIGTERM) };
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while is_alive(pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    true
+}
+
+/// Pid of the running watcher, if any.
+pub fn running_pid(cfg: &Config) -> Option<i32> {
+    let mut file = File::options().read(true).write(true).open(cfg.pid_file()).ok()?;
+    // probe with a shared lock: it only fails while a watcher holds it
+    let free = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) } == 0;
+    if free {
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+        return legacy_watcher(&mut file);
+    }
+    read_pid(&mut file)
+}
+
+/// Ask the running watcher to exit. Returns its pid, or None if none was running.
+pub fn stop(cfg: &Config) -> Result<Option<i32>> {
+    let Some(pid) = running_pid(cfg) else {
+        return Ok(None);
+    };
+    if !terminate(pid) {
+        anyhow::bail!("glance watch (pid {pid}) did not exit after SIGTERM");
+    }
+    Ok(Some(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(dir: &tempfile::TempDir) -> Config {
+        Config { runtime_dir: dir.path().to_path_buf(), ..Default::default() }
+    }
+
+    #[test]
+    fn second_instance_is_refused_until_first_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = cfg(&dir);
+        assert_eq!(running_pid(&cfg), None);
+
+        let first = PidLock::acquire(&cfg, false).unwrap();
+        assert_eq!(running_pid(&cfg), Some(std::process::id() as i32));
+        let err = PidLock::acquire(&cfg, false).err().unwrap();
+        assert!(err.to_string().contains("already running"), "{err}");
+
+        drop(first);
+        assert_eq!(running_pid(&cfg), None);
+        assert!(PidLock::acquire(&cfg, false).is_ok());
+    }
+
+    #[test]
+    fn stale_pid_file_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = cfg(&dir);
+        // left behind by a crash: nobody holds the lock
+        std::fs::write(cfg.pid_file(), "999999999").unwrap();
+        assert_eq!(running_pid(&cfg), None);
+
+        let _lock = PidLock::acquire(&cfg, false).unwrap();
+        let pid = std::fs::read_to_string(cfg.pid_file()).unwrap();
+        assert_eq!(pid, std::process::id().to_string());
+    }
+}
@@ -0,0 +1,29 @@
+pub mod clock;
+pub mod config;
+pub mod copy;
+pub mod delete;
+pub mod dismiss;
+pub mod drag;
+pub mod get;
+pub mod init;
+pub mod instance;
+pub mod list;
+pub mod log;
+pub mod menu;
+pub mod move_to;
+pub mod pick;
+pub mod preview;
+pub mod rename;
+pub mod rules;
+pub mod runner;
+pub mod scroll;
+pub mod select;
+pub mod state;
+pub mod status;
+pub mod stop;
+pub mod systemd;
+pub mod thumbnail;
+pub mod trash;
+pub mod util;
+pub mod watch;
+pub mod watch_status;
@@ -0,0 +1,162 @@
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::state::{read_history, FileState, HistoryState};
+use crate::util::{human_size, print_stdout};
+use anyhow::Result;
+use serde_json::json;
+
+/// "42s", "5m", "3h", "2d": how long ago `time` was.
+pub fn age(now: f64, time: f64) -> String {
+    let secs = (now - time).max(0.0) as u64;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+fn exists(e: &FileState) -> bool {
+    !e.missing && e.paths().iter().any(|p| p.exists())
+}
+
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn render_table(history: &HistoryState, now: f64) -> String {
+    let mut rows = vec![[
+        "INDEX".to_string(),
+        "NAME".into(),
+        "SIZE".into(),
+        "AGE".into(),
+        "DIR".into(),
+        "PINNED".into(),
+        "EXISTS".into(),
+    ]];
+    for (i, e) in history.entries.iter().enumerate() {
+        rows.push([
+            i.to_string(),
+            e.name.clone(),
+            human_size(e.size),
+            age(now, e.time),
+            e.dir().map(|d| d.display().to_string()).unwrap_or_default(),
+            yes_no(e.pinned).into(),
+            yes_no(exists(e)).into(),
+        ]);
+    }
+
+    let mut widths = [0usize; 7];
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, w)| format!("{cell:<w$}"))
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+fn render_json(history: &HistoryState, now: f64) -> String {
+    let rows: Vec<_> = history
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            json!({
+                "index": i,
+                "name": e.name,
+                "path": e.path,
+                "files": e.paths(),
+                "size": e.size,
+                "time": e.time,
+                "age": now - e.time,
+                "dir": e.dir(),
+                "pinned": e.pinned,
+                "tags": e.tags,
+                "exists": exists(e),
+                "selected": i == history.selected,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&rows).unwrap()
+}
+
+pub fn run(cfg: &Config, as_json: bool) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let now = SystemClock.now();
+    if as_json {
+        print_stdout(&format!("{}\n", render_json(&history, now)))?;
+    } else if !history.entries.is_empty() {
+        print_stdout(&render_table(&history, now))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn history() -> HistoryState {
+        HistoryState {
+            entries: vec![
+                FileState {
+                    path: PathBuf::from("/nonexistent/Downloads/report.pdf"),
+                    name: "report.pdf".into(),
+                    size: 2048,
+                    time: 970.0,
+                    pinned: true,
+                    ..Default::default()
+                },
+                FileState {
+                    path: PathBuf::from("/nonexistent/shot.png"),
+                    name: "shot.png".into(),
+                    size: 10,
+                    time: 1000.0 - 7200.0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ages_round_down_to_the_largest_unit() {
+        assert_eq!(age(100.0, 58.5), "41s");
+        assert_eq!(age(1000.0, 100.0), "15m");
+        assert_eq!(age(100_000.0, 0.0), "1d");
+        assert_eq!(age(0.0, 5.0), "0s");
+    }
+
+    #[test]
+    fn table_has_aligned_columns() {
+        let table = render_table(&history(), 1000.0);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "INDEX  NAME        SIZE    AGE  DIR                     PINNED  EXISTS");
+        assert_eq!(lines[1], "0      report.pdf  2.0 KB  30s  /nonexistent/Downloads  yes     no");
+        assert_eq!(lines[2], "1      shot.png    10 B    2h   /nonexistent            no      no");
+    }
+
+    #[test]
+    fn json_lists_every_entry() {
+        let rows: serde_json::Value = serde_json::from_str(&render_json(&history(), 1000.0)).unwrap();
+        assert_eq!(rows[0]["index"], 0);
+        assert_eq!(rows[0]["pinned"], true);
+        assert_eq!(rows[0]["selected"], true);
+        assert_eq!(rows[0]["age"], 30.0);
+        assert_eq!(rows[1]["dir"], "/nonexistent");
+        assert_eq!(rows[1]["files"], json!(["/nonexistent/shot.png"]));
+    }
+}
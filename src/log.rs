@@ -0,0 +1,251 @@
+//! Leveled, structured diagnostics.
+//!
+//! Every line has a level, the module it came from, a message and optional
+//! `key=value` fields. The level threshold comes from `$GLANCE_LOG` (`error`,
+//! `warn`, `info`, `debug`; default `info`). Output goes to stderr, or to the
+//! file given with `--log-file`, as text or (`--log-json`) one JSON object per
+//! line. When stderr is connected to the journal (the watcher running as a
+//! systemd user unit), text lines carry an sd-daemon `<N>` prefix so journald
+//! files them at the right priority.
+
+use serde_json::{json, Map, Value};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" | "trace" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    /// syslog(3) priority, as understood by journald.
+    fn priority(self) -> u8 {
+        match self {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug => 7,
+        }
+    }
+}
+
+struct Logger {
+    max: Level,
+    json: bool,
+    file: Option<Mutex<File>>,
+    journal: bool,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+fn env_level() -> Level {
+    std::env::var("GLANCE_LOG")
+        .ok()
+        .and_then(|s| Level::parse(&s))
+        .unwrap_or(Level::Info)
+}
+
+/// Set up logging for this process. Call once, before anything logs;
+/// without it, text goes to stderr at the `$GLANCE_LOG` level.
+pub fn init(file: Option<&Path>, json: bool) -> anyhow::Result<()> {
+    let file = match file {
+        Some(path) => Some(Mutex::new(
+            File::options().create(true).append(true).open(path)?,
+        )),
+        None => None,
+    };
+    let logger = Logger {
+        max: env_level(),
+        json,
+        journal: file.is_none() && stderr_is_journal(),
+        file,
+    };
+    LOGGER
+        .set(logger)
+        .map_err(|_| anyhow::anyhow!("logging initialised twice"))
+}
+
+fn logger() -> &'static Logger {
+    LOGGER.get_or_init(|| Logger {
+        max: env_level(),
+        json: false,
+        file: None,
+        journal: stderr_is_journal(),
+    })
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= logger().max
+}
+
+/// `$JOURNAL_STREAM` is `device:inode` of the journal socket; only trust it
+/// if stderr is still that socket (not redirected by a wrapper script).
+fn stderr_is_journal() -> bool {
+    let Ok(stream) = std::env::var("JOURNAL_STREAM") else {
+        return false;
+    };
+    let Some((dev, ino)) = stream.split_once(':') else {
+        return false;
+    };
+    let mut st: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(std::io::stderr().as_raw_fd(), &mut st) } != 0 {
+        return false;
+    }
+    dev.parse() == Ok(st.st_dev as u64) && ino.parse() == Ok(st.st_ino as u64)
+}
+
+/// `key=value`, quoting values that would otherwise be ambiguous.
+fn text_field(out: &mut String, key: &str, value: &str) {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        out.push_str(&format!(" {key}={value:?}"));
+    } else {
+        out.push_str(&format!(" {key}={value}"));
+    }
+}
+
+fn format_line(
+    json: bool,
+    level: Level,
+    target: &str,
+    msg: &str,
+    fields: &[(&str, &dyn Display)],
+) -> String {
+    if json {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        let mut obj = Map::new();
+        obj.insert("ts".into(), json!(ts));
+        obj.insert("level".into(), json!(level.as_str()));
+        obj.insert("target".into(), json!(target));
+        obj.insert("msg".into(), json!(msg));
+        for (k, v) in fields {
+            obj.insert((*k).into(), Value::String(v.to_string()));
+        }
+        Value::Object(obj).to_string()
+    } else {
+        let mut line = msg.to_string();
+        for (k, v) in fields {
+            text_field(&mut line, k, &v.to_string());
+        }
+        line
+    }
+}
+
+/// Backend for the macros; use those instead.
+pub fn emit(level: Level, target: &str, msg: fmt::Arguments, fields: &[(&str, &dyn Display)]) {
+    let logger = logger();
+    if level > logger.max {
+        return;
+    }
+    let line = format_line(logger.json, level, target, &msg.to_string(), fields);
+    match &logger.file {
+        Some(file) => {
+            if let Ok(mut f) = file.lock() {
+                // nowhere left to report a failing log file
+                let _ = writeln!(f, "{line}");
+            }
+        }
+        None if logger.journal && !logger.json => eprintln!("<{}>{line}", level.priority()),
+        None => eprintln!("{line}"),
+    }
+}
+
+/// `log::info!("new file"; path = p.display(), size = n)`: a format string
+/// and its arguments, then optionally `;` and structured fields.
+macro_rules! log_at {
+    ($level:expr, $fmt:literal $(, $arg:expr)* $(,)? $(; $($key:ident = $val:expr),+ $(,)?)?) => {
+        if $crate::log::enabled($level) {
+            $crate::log::emit(
+                $level,
+                module_path!(),
+                format_args!($fmt $(, $arg)*),
+                &[$($((stringify!($key), &$val as &dyn ::std::fmt::Display)),+)?],
+            )
+        }
+    };
+}
+
+macro_rules! error {
+    ($($t:tt)*) => { $crate::log::log_at!($crate::log::Level::Error, $($t)*) };
+}
+
+macro_rules! warning {
+    ($($t:tt)*) => { $crate::log::log_at!($crate::log::Level::Warn, $($t)*) };
+}
+
+macro_rules! info {
+    ($($t:tt)*) => { $crate::log::log_at!($crate::log::Level::Info, $($t)*) };
+}
+
+macro_rules! debug {
+    ($($t:tt)*) => { $crate::log::log_at!($crate::log::Level::Debug, $($t)*) };
+}
+
+// `warn` itself would clash with the built-in lint attribute
+pub(crate) use {debug, error, info, log_at, warning as warn};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_parse_and_order() {
+        assert_eq!(Level::parse("DEBUG"), Some(Level::Debug));
+        assert_eq!(Level::parse("warning"), Some(Level::Warn));
+        assert_eq!(Level::parse("loud"), None);
+        assert!(Level::Error < Level::Info);
+    }
+
+    #[test]
+    fn text_lines_append_quoted_fields() {
+        let path = "/tmp/my shot.png";
+        let line = format_line(
+            false,
+            Level::Debug,
+            "wayglance::watch",
+            "ignored",
+            &[("reason", &"dotfile"), ("path", &path)],
+        );
+        assert_eq!(line, r#"ignored reason=dotfile path="/tmp/my shot.png""#);
+    }
+
+    #[test]
+    fn json_lines_carry_level_target_and_fields() {
+        let line = format_line(true, Level::Warn, "wayglance::state", "corrupt", &[("n", &3)]);
+        let v: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["level"], "warn");
+        assert_eq!(v["target"], "wayglance::state");
+        assert_eq!(v["msg"], "corrupt");
+        assert_eq!(v["n"], "3");
+        assert!(v["ts"].as_f64().unwrap() > 0.0);
+    }
+}
@@ -1,67 +1,189 @@
-mod config;
-mod copy;
-mod drag;
-mod init;
-mod menu;
-mod scroll;
-mod state;
-mod status;
-mod util;
-mod watch;
-mod watch_status;
-
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use wayglance::select::Selector;
+use wayglance::{
+    config, copy, delete, dismiss, drag, get, init, list, log, menu, move_to, pick, rename, rules,
+    scroll, status, stop, trash, watch, watch_status,
+};
 
 #[derive(Parser)]
 #[command(name = "glance", about = "A file clipboard for Wayland")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Append log lines to this file instead of stderr (level: $GLANCE_LOG)
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Log one JSON object per line
+    #[arg(long, global = true)]
+    log_json: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Run the inotify watcher daemon
-    Watch,
+    Watch {
+        /// Stop an already running watcher and take over
+        #[arg(long)]
+        replace: bool,
+        /// Report whether a watcher is running instead of starting one
+        #[arg(long, conflicts_with = "replace")]
+        status: bool,
+    },
+    /// Stop the running watcher
+    Stop,
     /// Output status JSON for Waybar
     Status {
-        #[arg(long)]
-        index: Option<usize>,
+        #[command(flatten)]
+        entry: Selector,
     },
     /// Copy latest file path to clipboard via wl-copy
-    Copy,
+    Copy {
+        /// Copy the file(s) into this directory instead
+        #[arg(long, value_name = "DIR")]
+        to: Option<PathBuf>,
+        #[command(flatten)]
+        entry: Selector,
+    },
+    /// Move a history entry's files to the trash
+    Trash {
+        /// Put back what the last `glance trash` moved
+        #[arg(long, conflicts_with = "entry")]
+        undo: bool,
+        #[command(flatten)]
+        entry: Selector,
+    },
+    /// Permanently delete a history entry's files
+    Delete {
+        /// Don't ask first
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        entry: Selector,
+    },
+    /// Rename a history entry's file in place
+    Rename {
+        /// The new file name, without a directory
+        new_name: String,
+        #[command(flatten)]
+        entry: Selector,
+    },
+    /// Move a history entry's files to another directory
+    Move {
+        /// Destination directory
+        #[arg(long, value_name = "DIR")]
+        to: PathBuf,
+        #[command(flatten)]
+        entry: Selector,
+    },
     /// Launch drag-and-drop overlay at cursor
-    Drag,
+    Drag {
+        /// Drag every file in history
+        #[arg(long, conflicts_with_all = ["indices", "entry"])]
+        all: bool,
+        /// Drag these history entries (0 = newest), e.g. 0,2,3
+        #[arg(long, value_delimiter = ',', conflicts_with = "entry")]
+        indices: Vec<usize>,
+        #[command(flatten)]
+        entry: Selector,
+    },
     /// Show dropdown menu below Waybar with actions
-    Menu,
+    Menu {
+        #[command(flatten)]
+        entry: Selector,
+    },
+    /// Choose a history entry in rofi/fuzzel/wofi/dmenu, then act on it
+    Pick {
+        #[arg(value_enum, default_value = "copy")]
+        action: pick::Action,
+        /// Run this command with the chosen path(s) appended instead
+        #[arg(long, value_name = "CMD", conflicts_with = "action")]
+        exec: Option<String>,
+    },
     /// Scroll through file history (up/down)
     Scroll {
         direction: String,
     },
+    /// Hide the widget until the next new file or scroll
+    Dismiss,
+    /// Print the history as a table (or JSON) for scripts
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print one field of a history entry (default: the selected one)
+    Get {
+        /// History index, 0 = newest
+        #[arg(id = "position", value_name = "INDEX", conflicts_with = "entry")]
+        index: Option<usize>,
+        #[command(flatten)]
+        entry: Selector,
+        #[arg(long, value_enum, default_value = "path")]
+        field: get::Field,
+    },
+    /// Work with the `[[rules]]` applied to new files
+    Rules {
+        #[command(subcommand)]
+        command: rules::RulesCommand,
+    },
     /// Continuous status output for Waybar (watches state file)
     WatchStatus,
     /// Set up config, Waybar module, CSS, and Hyprland autostart
-    Init,
+    Init {
+        /// Start the watcher from a systemd user unit instead of exec-once
+        #[arg(long)]
+        systemd: bool,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    log::init(cli.log_file.as_deref(), cli.log_json)?;
 
-    if matches!(cli.command, Commands::Init) {
-        return init::run();
+    if let Commands::Init { systemd } = cli.command {
+        return init::run(systemd);
     }
 
     let cfg = config::Config::load()?;
 
     match cli.command {
-        Commands::Watch => watch::run(&cfg),
-        Commands::Status { index } => status::run(&cfg, index),
-        Commands::Copy => copy::run(&cfg),
-        Commands::Drag => drag::run(&cfg),
-        Commands::Menu => menu::run(&cfg),
+        Commands::Watch { status: true, .. } => {
+            // exit 1 when no watcher is running
+            if !watch::status(&cfg) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Commands::Watch { replace, .. } => watch::run(&cfg, replace),
+        Commands::Stop => stop::run(&cfg),
+        Commands::Status { ref entry } => status::run(&cfg, entry),
+        Commands::Copy { to: Some(ref dir), ref entry } => move_to::run_copy(&cfg, dir, entry),
+        Commands::Copy { to: None, ref entry } => copy::run(&cfg, entry),
+        Commands::Trash { undo, ref entry } => trash::run(&cfg, entry, undo),
+        Commands::Delete { yes, ref entry } => delete::run(&cfg, entry, yes),
+        Commands::Rename { ref new_name, ref entry } => rename::run(&cfg, new_name, entry),
+        Commands::Move { ref to, ref entry } => move_to::run(&cfg, to, entry),
+        Commands::Drag { all, ref indices, ref entry } => drag::run(&cfg, all, indices, entry),
+        Commands::Menu { ref entry } => menu::run(&cfg, entry),
+        Commands::Pick { action, ref exec } => pick::run(&cfg, action, exec.as_deref()),
         Commands::Scroll { ref direction } => scroll::run(&cfg, direction),
+        Commands::Dismiss => dismiss::run(&cfg),
+        Commands::List { json } => list::run(&cfg, json),
+        Commands::Get { index, ref entry, field } => get::run(&cfg, index, entry, field),
+        Commands::Rules { ref command } => rules::run(&cfg, command),
         Commands::WatchStatus => watch_status::run(&cfg),
-        Commands::Init => unreachable!(),
+        Commands::Init { .. } => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
     }
 }
@@ -1,6 +1,21 @@
-use crate::config::Config;
-use crate::state::read_history;
-use crate::util::{cursor_pos, find_monitor_at, human_size};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{Config, MenuKeys};
+use crate::copy::path_list;
+use crate::delete::delete_entry;
+use crate::drag::content_provider;
+use crate::log;
+use crate::move_to::{destinations, move_entry};
+use crate::preview::{self, Limits, Preview};
+use crate::rename::rename_entry;
+use crate::runner;
+use crate::select::Selector;
+use crate::state::{read_history, with_history, FileState};
+use crate::thumbnail;
+use crate::trash::{self, Undo};
+use crate::util::{
+    cursor_pos, escape_markup, existing_paths, find_monitor_at, human_size, remove_stale,
+    signal_waybar,
+};
 use anyhow::Result;
 use gtk4::gdk;
 use gtk4::gdk_pixbuf::Pixbuf;
@@ -8,29 +23,20 @@ use gtk4::gio;
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4_layer_shell::{Edge, Layer, LayerShell};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::rc::Rc;
 use std::time::Duration;
 
 const THUMB_MAX: i32 = 150;
 const MENU_W: i32 = 220;
-const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "svg", "tiff"];
-
-fn is_image(path: &std::path::Path) -> bool {
-    path.extension()
-        .and_then(|e| e.to_str())
-        .map(|e| IMAGE_EXTS.contains(&e.to_lowercase().as_str()))
-        .unwrap_or(false)
-}
-
-fn menu_lock_path() -> std::path::PathBuf {
-    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
-    std::path::PathBuf::from(runtime).join("glance-menu.lock")
-}
-
-fn menu_pos_path() -> std::path::PathBuf {
-    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
-    std::path::PathBuf::from(runtime).join("glance-menu-pos")
-}
+const PROGRESS_TICK_MS: u64 = 50;
+const BATCH_LIST_MAX: usize = 12;
+/// Thumbnail size in the history panel.
+const ROW_THUMB: i32 = 28;
+/// Height the history panel grows to before it scrolls.
+const HISTORY_MAX_H: i32 = 240;
 
 fn read_saved_pos(path: &std::path::Path) -> Option<(i32, i32)> {
     let content = std::fs::read_to_string(path).ok()?;
@@ -43,27 +49,26 @@ fn read_saved_pos(path: &std::path::Path) -> Option<(i32, i32)> {
 }
 
 fn save_pos(path: &std::path::Path, x: i32, y: i32) {
-    let _ = std::fs::write(path, format!("{x},{y}"));
-}
-
-fn menu_pid_path() -> std::path::PathBuf {
-    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
-    std::path::PathBuf::from(runtime).join("glance-menu.pid")
+    if let Err(e) = std::fs::write(path, format!("{x},{y}")) {
+        log::warn!("failed to save menu position: {e}"; path = path.display());
+    }
 }
 
-fn kill_existing_menu() {
-    if let Ok(pid_str) = std::fs::read_to_string(menu_pid_path()) {
+fn kill_existing_menu(cfg: &Config) {
+    if let Ok(pid_str) = std::fs::read_to_string(cfg.menu_pid_file()) {
         if let Ok(pid) = pid_str.trim().parse::<i32>() {
             unsafe { libc::kill(pid, libc::SIGTERM); }
             std::thread::sleep(Duration::from_millis(50));
         }
     }
-    let _ = std::fs::remove_file(menu_pid_path());
-    let _ = std::fs::remove_file(menu_lock_path());
+    remove_stale(&cfg.menu_pid_file());
+    remove_stale(&cfg.menu_lock_file());
 }
 
-fn write_menu_pid() {
-    let _ = std::fs::write(menu_pid_path(), std::process::id().to_string());
+fn write_menu_pid(cfg: &Config) {
+    if let Err(e) = std::fs::write(cfg.menu_pid_file(), std::process::id().to_string()) {
+        log::warn!("failed to write menu pid file: {e}");
+    }
 }
 
 fn editor_prompted_path() -> std::path::PathBuf {
@@ -76,14 +81,6 @@ fn editor_prompted_path() -> std::path::PathBuf {
     config_dir.join("glance/.editor-prompted")
 }
 
-fn editor_exists(bin: &str) -> bool {
-    Command::new("which")
-        .arg(bin)
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
-}
-
 /// Parse the editor config string into (binary, args).
 fn parse_editor(editor: &str) -> (&str, Vec<&str>) {
     let mut parts = editor.split_whitespace();
@@ -94,36 +91,37 @@ fn parse_editor(editor: &str) -> (&str, Vec<&str>) {
 
 /// Check if the configured editor binary is available. If not installed,
 /// show a one-time notification and fall back to xdg-open.
-fn resolve_editor(editor: &str) -> (String, Vec<String>) {
+pub fn resolve_editor(editor: &str) -> (String, Vec<String>) {
     let (bin, args) = parse_editor(editor);
-    if editor_exists(bin) {
+    if runner::installed(bin) {
         return (bin.to_string(), args.iter().map(|s| s.to_string()).collect());
     }
 
     // only prompt once
     let prompted = editor_prompted_path();
     if !prompted.exists() {
-        if let Some(parent) = prompted.parent() {
-            let _ = std::fs::create_dir_all(parent);
+        let marked = match prompted.parent() {
+            Some(parent) => std::fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|_| std::fs::write(&prompted, ""));
+        if let Err(e) = marked {
+            log::warn!("failed to remember the editor prompt: {e}"; path = prompted.display());
         }
-        let _ = std::fs::write(&prompted, "");
 
         let msg = if bin == "swappy" {
-            format!(
-                "swappy is not installed. Install it for screenshot editing:\n\
-                 sudo dnf install swappy\n\n\
-                 Falling back to xdg-open. You can change the editor in\n\
-                 ~/.config/glance/config.toml"
-            )
+            "swappy is not installed. Install it for screenshot editing:\n\
+             sudo dnf install swappy\n\n\
+             Falling back to xdg-open. You can change the editor in\n\
+             ~/.config/glance/config.toml"
+                .to_string()
         } else {
             format!(
                 "{bin} is not installed. Falling back to xdg-open.\n\
                  You can change the editor in ~/.config/glance/config.toml"
             )
         };
-        let _ = Command::new("notify-send")
-            .args(["glance", &msg])
-            .spawn();
+        runner::launch(Command::new("notify-send").args(["glance", &msg]));
     }
 
     ("xdg-open".to_string(), vec![])
@@ -140,9 +138,26 @@ fn build_css(cfg: &Config) -> String {
          .menu-action {{ background: {bb}; color: {tc}; \
            border: none; border-radius: 8px; padding: 6px 14px; min-height: 0; min-width: 0; }} \
          .menu-action:hover {{ background: {bh}; }} \
+         .menu-history {{ color: {sc}; font-size: 11px; margin-top: 8px; }} \
+         .menu-history list {{ background: none; }} \
+         .menu-row {{ border-radius: 6px; padding: 2px 4px; }} \
+         .menu-row:selected {{ background: {bb}; }} \
+         .menu-row:hover {{ background: {bh}; }} \
+         .menu-row-name {{ color: {tc}; font-size: 11px; }} \
+         .menu-row-missing {{ opacity: 0.4; }} \
          .menu-close {{ background: none; border: none; color: {sc}; \
            min-height: 0; min-width: 0; padding: 2px 6px; }} \
-         .menu-close:hover {{ color: #f38ba8; }}",
+         .menu-close:hover {{ color: #f38ba8; }} \
+         .menu-danger {{ background: #f38ba8; color: {bg}; }} \
+         .menu-rename {{ font-size: 11px; margin-top: 6px; }} \
+         .menu-undo {{ margin-top: 6px; }} \
+         .menu-move {{ margin-top: 6px; }} \
+         .menu-text-box {{ background: {bb}; border-radius: 6px; padding: 6px 8px; }} \
+         .menu-text {{ font-family: monospace; font-size: 10px; color: {tc}; }} \
+         .menu-cell-head {{ font-weight: bold; }} \
+         .menu-progress {{ margin-top: 8px; }} \
+         .menu-progress trough {{ min-height: 2px; background: {bb}; border-radius: 1px; }} \
+         .menu-progress progress {{ min-height: 2px; background: {sc}; border-radius: 1px; }}",
         bg = s.background,
         br = s.border_radius,
         sc = s.secondary_color,
@@ -152,31 +167,400 @@ fn build_css(cfg: &Config) -> String {
     )
 }
 
-pub fn run(cfg: &Config) -> Result<()> {
-    let history = read_history(&Config::state_file());
-    let manually_scrolled = history.selected != 0;
-    let Some(st) = history.current().filter(|e| manually_scrolled || !e.is_expired(cfg.dismiss_seconds)) else {
+/// `name`, cut to `max` characters with an ellipsis.
+fn truncate_name(name: &str, max: usize) -> String {
+    if name.chars().count() > max {
+        let head: String = name.chars().take(max - 1).collect();
+        format!("{head}\u{2026}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// How a key name reads on a button.
+fn key_symbol(name: &str) -> String {
+    match name {
+        "Return" | "KP_Enter" => "\u{23ce}".into(),
+        "Delete" => "Del".into(),
+        "BackSpace" => "\u{232b}".into(),
+        "space" => "\u{2423}".into(),
+        _ => name.to_string(),
+    }
+}
+
+/// Button markup with its first shortcut shown: a letter of the label is
+/// underlined, any other key is appended.
+fn key_hint(label: &str, keys: &[String]) -> String {
+    let Some(key) = keys.first() else {
+        return escape_markup(label);
+    };
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if let Some(at) = label.find(|l: char| l.eq_ignore_ascii_case(&c)) {
+            let hit = label[at..].chars().next().unwrap_or(c);
+            let (before, after) = (&label[..at], &label[at + hit.len_utf8()..]);
+            return format!(
+                "{}<u>{}</u>{}",
+                escape_markup(before),
+                escape_markup(&hit.to_string()),
+                escape_markup(after)
+            );
+        }
+    }
+    format!("{} <small>{}</small>", escape_markup(label), escape_markup(&key_symbol(key)))
+}
+
+fn set_hint(btn: &gtk4::Button, label: &str, keys: &[String]) {
+    let text = gtk4::Label::new(None);
+    text.set_markup(&key_hint(label, keys));
+    btn.set_child(Some(&text));
+}
+
+fn action_button(label: &str, keys: &[String]) -> gtk4::Button {
+    let btn = gtk4::Button::new();
+    set_hint(&btn, label, keys);
+    btn.add_css_class("menu-action");
+    btn
+}
+
+/// A directory as the menu shows it, with the home directory as `~`.
+fn dir_label(dir: &Path, home: &Path) -> String {
+    match dir.strip_prefix(home) {
+        _ if home.as_os_str().is_empty() => dir.display().to_string(),
+        Ok(rest) if rest.as_os_str().is_empty() => "~".into(),
+        Ok(rest) => format!("~/{}", rest.display()),
+        Err(_) => dir.display().to_string(),
+    }
+}
+
+/// A history entry and whichever of its files are still on disk.
+struct Item {
+    entry: FileState,
+    paths: Vec<PathBuf>,
+}
+
+/// The open menu's view of history and which entry it is showing.
+struct MenuState {
+    items: Vec<Item>,
+    shown: usize,
+}
+
+impl MenuState {
+    fn current(&self) -> &Item {
+        &self.items[self.shown]
+    }
+
+    /// The files still on disk of the entries at `indices`, each once.
+    fn paths_of(&self, indices: impl IntoIterator<Item = usize>) -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = Vec::new();
+        for p in indices.into_iter().flat_map(|i| &self.items[i].paths) {
+            if !out.contains(p) {
+                out.push(p.clone());
+            }
+        }
+        out
+    }
+
+    /// Everything in history, for dragging the whole set at once.
+    fn all_paths(&self) -> Vec<PathBuf> {
+        self.paths_of(0..self.items.len())
+    }
+
+    /// The nearest entry with files left, older or newer than the shown one.
+    fn step(&self, older: bool) -> Option<usize> {
+        let usable = |&i: &usize| !self.items[i].paths.is_empty();
+        if older {
+            (self.shown + 1..self.items.len()).find(usable)
+        } else {
+            (0..self.shown).rev().find(usable)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum KeyAction {
+    Older,
+    Newer,
+    Open,
+    Copy,
+    Edit,
+    Drag,
+    Pin,
+    Rename,
+    Move,
+    Trash,
+    Delete,
+    Undo,
+    Close,
+}
+
+/// Resolve the configured key names; unknown ones are reported and skipped.
+fn key_bindings(keys: &MenuKeys) -> Vec<(gdk::Key, KeyAction)> {
+    let groups = [
+        (&keys.older, KeyAction::Older),
+        (&keys.newer, KeyAction::Newer),
+        (&keys.open, KeyAction::Open),
+        (&keys.copy, KeyAction::Copy),
+        (&keys.edit, KeyAction::Edit),
+        (&keys.drag, KeyAction::Drag),
+        (&keys.pin, KeyAction::Pin),
+        (&keys.rename, KeyAction::Rename),
+        (&keys.move_to, KeyAction::Move),
+        (&keys.trash, KeyAction::Trash),
+        (&keys.delete, KeyAction::Delete),
+        (&keys.undo, KeyAction::Undo),
+        (&keys.close, KeyAction::Close),
+    ];
+    let mut out = Vec::new();
+    for (names, action) in groups {
+        for name in names {
+            match gdk::Key::from_name(name.as_str()) {
+                Some(key) => out.push((key.to_lower(), action)),
+                None => log::warn!("unknown key in [menu_keys]"; key = name),
+            }
+        }
+    }
+    out
+}
+
+/// The themed icon for the file's type, e.g. a film strip for videos.
+fn mime_icon(path: &Path, size: i32) -> gtk4::Image {
+    let (content_type, _) = gio::content_type_guess(Some(path), None);
+    let icon = gtk4::Image::from_gicon(&gio::content_type_get_icon(&content_type));
+    icon.set_pixel_size(size);
+    icon
+}
+
+/// A preview of the entry. Starts as the icon for its type and is swapped
+/// for the thumbnail once that has loaded in the background.
+fn entry_icon(entry: &FileState, cache: &Path, thumb_max: i32, icon_size: i32) -> gtk4::Widget {
+    if entry.is_batch() {
+        let icon = gtk4::Image::from_icon_name("folder");
+        icon.set_pixel_size(icon_size);
+        return icon.upcast();
+    }
+    let slot = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    slot.set_halign(gtk4::Align::Center);
+    slot.append(&mime_icon(&entry.path, icon_size));
+
+    let (path, cache, s) = (entry.path.clone(), cache.to_path_buf(), slot.clone());
+    glib::spawn_future_local(async move {
+        let prepared = gio::spawn_blocking(move || thumbnail::prepare(&cache, &path, thumb_max)).await;
+        let Ok(Some(small)) = prepared else { return };
+        // the preview may have moved on to another entry; then this slot is
+        // no longer shown and updating it is harmless
+        if let Ok(pixbuf) = Pixbuf::from_file_at_scale(&small, thumb_max, thumb_max, true) {
+            let picture = gtk4::Picture::for_paintable(&gdk::Texture::for_pixbuf(&pixbuf));
+            picture.set_size_request(pixbuf.width(), pixbuf.height());
+            while let Some(child) = s.first_child() {
+                s.remove(&child);
+            }
+            s.append(&picture);
+        }
+    });
+    slot.upcast()
+}
+
+/// The first lines of a text file in monospace, or a CSV as a grid.
+fn text_view(text: &Preview) -> gtk4::Widget {
+    let view: gtk4::Widget = match text {
+        Preview::Text(text) => {
+            let label = gtk4::Label::new(Some(text));
+            label.add_css_class("menu-text");
+            label.set_xalign(0.0);
+            label.upcast()
+        }
+        Preview::Table(rows) => {
+            let grid = gtk4::Grid::new();
+            grid.set_column_spacing(8);
+            for (r, row) in rows.iter().enumerate() {
+                for (c, cell) in row.iter().enumerate() {
+                    let label = gtk4::Label::new(Some(cell));
+                    label.add_css_class("menu-text");
+                    if r == 0 {
+                        label.add_css_class("menu-cell-head");
+                    }
+                    label.set_xalign(0.0);
+                    grid.attach(&label, c as i32, r as i32, 1, 1);
+                }
+            }
+            grid.upcast()
+        }
+    };
+    let frame = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    frame.add_css_class("menu-text-box");
+    frame.append(&view);
+    frame.upcast()
+}
+
+/// Replace the preview's contents with `item`: thumbnail (or the start of a
+/// text file), name, size and, for a batch, its files.
+fn fill_preview(preview: &gtk4::Box, item: &Item, limits: Limits, cache: &Path) {
+    let Item { entry, paths } = item;
+    while let Some(child) = preview.first_child() {
+        preview.remove(&child);
+    }
+
+    // a text file is read in the background, with its type's icon standing
+    // in meanwhile and kept if it turns out not to be text after all
+    let slot = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    if !entry.is_batch() && limits.lines > 0 && preview::is_text(&entry.path) {
+        slot.append(&mime_icon(&entry.path, 48));
+        let (entry, cache, s) = (entry.clone(), cache.to_path_buf(), slot.clone());
+        glib::spawn_future_local(async move {
+            let path = entry.path.clone();
+            let loaded = gio::spawn_blocking(move || preview::load(&path, limits)).await;
+            let view = match loaded {
+                Ok(Some(text)) => text_view(&text),
+                _ => entry_icon(&entry, &cache, THUMB_MAX, 48),
+            };
+            while let Some(child) = s.first_child() {
+                s.remove(&child);
+            }
+            s.append(&view);
+        });
+    } else {
+        slot.append(&entry_icon(entry, cache, THUMB_MAX, 48));
+    }
+    preview.append(&slot);
+
+    let name_label = gtk4::Label::new(Some(&truncate_name(&entry.name, 24)));
+    name_label.add_css_class("menu-name");
+    name_label.set_tooltip_text(Some(&entry.name));
+    preview.append(&name_label);
+
+    let mut size = human_size(entry.size);
+    if entry.pinned {
+        size.push_str(" \u{b7} pinned");
+    }
+    let size_label = gtk4::Label::new(Some(&size));
+    size_label.add_css_class("menu-size");
+    preview.append(&size_label);
+
+    if entry.is_batch() {
+        let list = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+        for p in paths.iter().take(BATCH_LIST_MAX) {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            let label = gtk4::Label::new(Some(&name));
+            label.add_css_class("menu-size");
+            label.set_xalign(0.0);
+            label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+            list.append(&label);
+        }
+        if paths.len() > BATCH_LIST_MAX {
+            let more = format!("\u{2026}and {} more", paths.len() - BATCH_LIST_MAX);
+            let label = gtk4::Label::new(Some(&more));
+            label.add_css_class("menu-size");
+            label.set_xalign(0.0);
+            list.append(&label);
+        }
+        let expander = gtk4::Expander::new(Some("Show files"));
+        expander.add_css_class("menu-size");
+        expander.set_child(Some(&list));
+        preview.append(&expander);
+    }
+}
+
+/// Grey out a history row whose files are gone, or bring it back.
+fn set_row_usable(row: &gtk4::ListBoxRow, usable: bool) {
+    if usable {
+        row.remove_css_class("menu-row-missing");
+    } else {
+        row.add_css_class("menu-row-missing");
+    }
+    row.set_activatable(usable);
+    row.set_selectable(usable);
+}
+
+/// Rows picked in the history list, in index order.
+fn selected_indices(list: &gtk4::ListBox) -> Vec<usize> {
+    let mut picked: Vec<usize> = list.selected_rows().iter().map(|r| r.index() as usize).collect();
+    picked.sort_unstable();
+    picked
+}
+
+/// One line of the history panel: index, small thumbnail, name and size.
+fn history_row(index: usize, item: &Item, cache: &Path) -> gtk4::ListBoxRow {
+    let line = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+    let number = gtk4::Label::new(Some(&index.to_string()));
+    number.add_css_class("menu-size");
+    number.set_width_chars(2);
+    line.append(&number);
+    let icon = entry_icon(&item.entry, cache, ROW_THUMB, ROW_THUMB);
+    icon.set_size_request(ROW_THUMB, ROW_THUMB);
+    line.append(&icon);
+    let name = gtk4::Label::new(Some(&item.entry.name));
+    name.add_css_class("menu-row-name");
+    name.set_xalign(0.0);
+    name.set_hexpand(true);
+    name.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+    line.append(&name);
+    let size = gtk4::Label::new(Some(&human_size(item.entry.size)));
+    size.add_css_class("menu-size");
+    line.append(&size);
+
+    let row = gtk4::ListBoxRow::new();
+    row.add_css_class("menu-row");
+    row.set_child(Some(&line));
+    row.set_tooltip_text(Some(&item.entry.path.to_string_lossy()));
+    if item.paths.is_empty() {
+        set_row_usable(&row, false);
+    }
+    row
+}
+
+/// Make `entry` the one the widget shows, as `glance scroll` would. Batches
+/// from one directory share a path, so it's matched on arrival time too.
+fn persist_selection(state_file: &Path, signal: u8, entry: &FileState) {
+    let saved = with_history(state_file, |h| {
+        if let Some(i) = h.entries.iter().position(|e| e.same_entry(entry)) {
+            h.select(i, &SystemClock);
+        }
+    });
+    match saved {
+        Ok(()) => signal_waybar(signal),
+        Err(e) => log::warn!("failed to save the selection: {e}"),
+    }
+}
+
+pub fn run(cfg: &Config, sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let Some(start) = sel.target_index(&history, SystemClock.now(), cfg)? else {
         return Ok(());
     };
-    if !st.path.exists() {
+    let items: Vec<Item> = history
+        .entries
+        .iter()
+        .map(|e| Item { entry: e.clone(), paths: existing_paths(&e.paths()) })
+        .collect();
+    if items[start].paths.is_empty() {
         return Ok(());
     }
+    let state = Rc::new(RefCell::new(MenuState { items, shown: start }));
 
-    let filepath = st.path.clone();
-    let filename = st.name.clone();
-    let filesize = st.size;
     let bar_height = cfg.bar_height;
     let menu_dismiss = cfg.menu_dismiss_seconds;
     let has_drag = cfg.has_action("drag");
     let has_open = cfg.has_action("open");
     let has_edit = cfg.has_action("edit");
     let has_copy = cfg.has_action("copy");
+    let has_rename = cfg.has_action("rename");
+    let has_trash = cfg.has_action("trash");
+    let has_delete = cfg.has_action("delete");
+    // "Move to…" only makes sense with somewhere to go
+    let dests = destinations(cfg);
+    let has_move = cfg.has_action("move") && !dests.is_empty();
+    let shared_cfg = Rc::new(cfg.clone());
     let editor_cmd = cfg.editor.clone();
     let drag_cmd = cfg.drag_command.clone();
+    let keys = cfg.menu_keys.clone();
+    let limits = Limits::from_config(cfg);
+    let state_file = cfg.state_file();
+    let signal = cfg.signal_number;
     let css_str = build_css(cfg);
 
     // use saved module position if available, otherwise capture from cursor
-    let pos_file = menu_pos_path();
+    let pos_file = cfg.menu_pos_file();
     let (cursor_x, cursor_y) = if let Some((x, y)) = read_saved_pos(&pos_file) {
         (x, y)
     } else {
@@ -187,12 +571,14 @@ pub fn run(cfg: &Config) -> Result<()> {
     let monitor_info = find_monitor_at(cursor_x, cursor_y);
 
     // kill any existing menu instance
-    kill_existing_menu();
-    write_menu_pid();
-    let _ = std::fs::write(menu_lock_path(), "");
+    kill_existing_menu(cfg);
+    write_menu_pid(cfg);
+    if let Err(e) = std::fs::write(cfg.menu_lock_file(), "") {
+        log::warn!("failed to write menu lock file: {e}");
+    }
 
     let app = gtk4::Application::builder()
-        .application_id(&format!("dev.glance.menu.{}", std::process::id()))
+        .application_id(format!("dev.glance.menu.{}", std::process::id()))
         .build();
 
     app.connect_activate(move |app| {
@@ -238,67 +624,121 @@ pub fn run(cfg: &Config) -> Result<()> {
 
         let container = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
 
-        // thumbnail or icon
-        if is_image(&filepath) {
-            if let Ok(pixbuf) = Pixbuf::from_file(&filepath) {
-                let (w, h) = (pixbuf.width(), pixbuf.height());
-                let scale = (THUMB_MAX as f64) / (w.max(h) as f64);
-                let new_w = ((w as f64 * scale) as i32).max(1);
-                let new_h = ((h as f64 * scale) as i32).max(1);
-                if let Some(scaled) = pixbuf.scale_simple(
-                    new_w,
-                    new_h,
-                    gtk4::gdk_pixbuf::InterpType::Bilinear,
-                ) {
-                    let texture = gdk::Texture::for_pixbuf(&scaled);
-                    let picture = gtk4::Picture::for_paintable(&texture);
-                    picture.set_size_request(new_w, new_h);
-                    container.append(&picture);
+        // thumbnail, name and size of the entry being shown
+        let preview = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+        container.append(&preview);
+
+        // actions, shared by the buttons and the keyboard; they act on
+        // whichever entry is shown at the time
+        // the history list is filled in further down; ctrl-clicked rows
+        // there are dragged together
+        let list = gtk4::ListBox::new();
+        let drag_all = gtk4::ToggleButton::new();
+        // counted by refresh, as trashing and moving change it
+        let drag_all_label = gtk4::Label::new(None);
+        drag_all.set_child(Some(&drag_all_label));
+        drag_all.add_css_class("menu-action");
+        drag_all.set_tooltip_text(Some("Drag every file in history"));
+        let drag_set = {
+            let (state, toggle) = (state.clone(), drag_all.clone());
+            let list = list.clone();
+            move || {
+                let picked = selected_indices(&list);
+                let st = state.borrow();
+                if toggle.is_active() {
+                    st.all_paths()
+                } else if picked.len() > 1 {
+                    st.paths_of(picked)
+                } else {
+                    st.current().paths.clone()
                 }
             }
+        };
+        let drag_external: Rc<dyn Fn()> = {
+            let (set, cmd, a) = (drag_set.clone(), drag_cmd.clone(), app_handle.clone());
+            Rc::new(move || {
+                let mut parts = cmd.split_whitespace();
+                let bin = parts.next().unwrap_or("ripdrag");
+                let args: Vec<&str> = parts.collect();
+                runner::launch(Command::new(bin).args(&args).args(set()));
+                a.quit();
+            })
+        };
+        // a builtin drag needs the pointer, so from the keyboard hand over
+        // to the overlay of `glance drag`
+        let drag_key: Rc<dyn Fn()> = if drag_cmd == "builtin" {
+            let (state, toggle, a) = (state.clone(), drag_all.clone(), app_handle.clone());
+            let list = list.clone();
+            Rc::new(move || {
+                let bin = std::env::current_exe().unwrap_or_else(|_| "glance".into());
+                let mut cmd = Command::new(bin);
+                cmd.arg("drag");
+                let picked = selected_indices(&list);
+                if toggle.is_active() {
+                    cmd.arg("--all");
+                } else if picked.len() > 1 {
+                    let picked: Vec<String> = picked.iter().map(usize::to_string).collect();
+                    cmd.arg("--indices").arg(picked.join(","));
+                } else {
+                    cmd.arg("--path").arg(&state.borrow().current().entry.path);
+                }
+                runner::launch(&mut cmd);
+                a.quit();
+            })
         } else {
-            let icon = gtk4::Image::from_icon_name("text-x-generic");
-            icon.set_pixel_size(48);
-            container.append(&icon);
-        }
-
-        // file name
-        let display_name = if filename.len() > 24 {
-            format!("{}\u{2026}", &filename[..21])
-        } else {
-            filename.clone()
+            drag_external.clone()
+        };
+        let open: Rc<dyn Fn()> = {
+            let (state, a) = (state.clone(), app_handle.clone());
+            Rc::new(move || {
+                // a batch opens its folder
+                runner::launch(Command::new("xdg-open").arg(&state.borrow().current().entry.path));
+                a.quit();
+            })
+        };
+        let edit: Rc<dyn Fn()> = {
+            let (state, editor, a) = (state.clone(), editor_cmd.clone(), app_handle.clone());
+            Rc::new(move || {
+                let path = {
+                    let st = state.borrow();
+                    if st.current().entry.is_batch() {
+                        return;
+                    }
+                    st.current().entry.path.clone()
+                };
+                let (bin, args) = resolve_editor(&editor);
+                runner::launch(Command::new(&bin).args(&args).arg(&path));
+                a.quit();
+            })
+        };
+        let copy: Rc<dyn Fn()> = {
+            let (state, a) = (state.clone(), app_handle.clone());
+            Rc::new(move || {
+                let text = {
+                    let st = state.borrow();
+                    let paths = &st.current().paths;
+                    path_list(&paths.iter().map(PathBuf::as_path).collect::<Vec<&Path>>())
+                };
+                runner::launch(Command::new("wl-copy").arg(&text));
+                a.quit();
+            })
         };
-        let name_label = gtk4::Label::new(Some(&display_name));
-        name_label.add_css_class("menu-name");
-        name_label.set_tooltip_text(Some(&filename));
-        container.append(&name_label);
-
-        // file size
-        let size_label = gtk4::Label::new(Some(&human_size(filesize)));
-        size_label.add_css_class("menu-size");
-        container.append(&size_label);
 
-        // action buttons
         let actions = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
         actions.add_css_class("menu-actions");
         actions.set_halign(gtk4::Align::Center);
 
-        // Drag
+        // Drag, with a toggle to take the whole history along
         if has_drag {
             if drag_cmd == "builtin" {
-                let btn_drag = gtk4::Label::new(Some("Drag"));
+                let btn_drag = gtk4::Label::new(None);
+                btn_drag.set_markup(&key_hint("Drag", &keys.drag));
                 btn_drag.add_css_class("menu-action");
                 btn_drag.set_size_request(60, -1);
                 let ds = gtk4::DragSource::new();
                 ds.set_actions(gdk::DragAction::COPY);
-                let file = gio::File::for_path(&filepath);
-                let uri = format!("{}\r\n", file.uri());
-                ds.connect_prepare(move |_, _, _| {
-                    Some(gdk::ContentProvider::for_bytes(
-                        "text/uri-list",
-                        &glib::Bytes::from(uri.as_bytes()),
-                    ))
-                });
+                let set = drag_set.clone();
+                ds.connect_prepare(move |_, _, _| Some(content_provider(&set())));
                 let a = app_handle.clone();
                 ds.connect_drag_end(move |_, _, _| {
                     let a = a.clone();
@@ -309,67 +749,413 @@ pub fn run(cfg: &Config) -> Result<()> {
                 btn_drag.add_controller(ds);
                 actions.append(&btn_drag);
             } else {
-                let btn_drag = gtk4::Button::with_label("Drag");
-                btn_drag.add_css_class("menu-action");
-                let p = filepath.clone();
-                let cmd = drag_cmd.clone();
-                let a = app_handle.clone();
-                btn_drag.connect_clicked(move |_| {
-                    let mut parts = cmd.split_whitespace();
-                    let bin = parts.next().unwrap_or("ripdrag");
-                    let args: Vec<&str> = parts.collect();
-                    let _ = Command::new(bin).args(&args).arg(&p).spawn();
-                    a.quit();
-                });
+                let btn_drag = action_button("Drag", &keys.drag);
+                let f = drag_external.clone();
+                btn_drag.connect_clicked(move |_| f());
                 actions.append(&btn_drag);
             }
+            actions.append(&drag_all);
         }
 
         // Open
         if has_open {
-            let btn_open = gtk4::Button::with_label("Open");
-            btn_open.add_css_class("menu-action");
-            let p = filepath.clone();
-            let a = app_handle.clone();
-            btn_open.connect_clicked(move |_| {
-                let _ = Command::new("xdg-open").arg(&p).spawn();
-                a.quit();
-            });
+            let btn_open = action_button("Open", &keys.open);
+            let f = open.clone();
+            btn_open.connect_clicked(move |_| f());
             actions.append(&btn_open);
         }
 
-        // Edit
+        // Edit, hidden while a batch is shown
+        let btn_edit = action_button("Edit", &keys.edit);
         if has_edit {
-            let btn_edit = gtk4::Button::with_label("Edit");
-            btn_edit.add_css_class("menu-action");
-            let p = filepath.clone();
-            let editor = editor_cmd.clone();
-            let a = app_handle.clone();
-            btn_edit.connect_clicked(move |_| {
-                let (bin, args) = resolve_editor(&editor);
-                let _ = Command::new(&bin).args(&args).arg(&p).spawn();
-                a.quit();
-            });
+            let f = edit.clone();
+            btn_edit.connect_clicked(move |_| f());
             actions.append(&btn_edit);
         }
 
         // Copy
         if has_copy {
-            let btn_copy = gtk4::Button::with_label("Copy");
-            btn_copy.add_css_class("menu-action");
-            let p = filepath.clone();
-            let a = app_handle.clone();
-            btn_copy.connect_clicked(move |_| {
-                let _ = Command::new("wl-copy")
-                    .arg(p.to_string_lossy().as_ref())
-                    .spawn();
-                a.quit();
-            });
+            let btn_copy = action_button("Copy", &keys.copy);
+            let f = copy.clone();
+            btn_copy.connect_clicked(move |_| f());
             actions.append(&btn_copy);
         }
 
         container.append(&actions);
 
+        // changing the file itself; wired up below, once the closures that
+        // move between entries exist
+        let manage = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        manage.add_css_class("menu-actions");
+        manage.set_halign(gtk4::Align::Center);
+        let btn_rename = action_button("Rename", &keys.rename);
+        let btn_trash = action_button("Trash", &keys.trash);
+        let btn_move = action_button("Move\u{2026}", &keys.move_to);
+        let btn_delete = action_button("Delete", &keys.delete);
+        let buttons = [
+            (has_rename, &btn_rename),
+            (has_move, &btn_move),
+            (has_trash, &btn_trash),
+            (has_delete, &btn_delete),
+        ];
+        for (shown, btn) in buttons {
+            if shown {
+                manage.append(btn);
+            }
+        }
+        if has_rename || has_move || has_trash || has_delete {
+            container.append(&manage);
+        }
+        // the first press of Delete only asks
+        let delete_armed = Rc::new(Cell::new(false));
+
+        let rename_field = gtk4::Entry::new();
+        rename_field.add_css_class("menu-rename");
+        rename_field.set_visible(false);
+        container.append(&rename_field);
+
+        // "Move to…" destinations, numbered for the keyboard
+        let move_list = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+        move_list.add_css_class("menu-move");
+        move_list.set_visible(false);
+        let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+        let dest_buttons: Vec<gtk4::Button> = dests
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| {
+                let label = format!("{}  {}", i + 1, dir_label(dir, &home));
+                let btn = action_button(&label, &[]);
+                btn.set_tooltip_text(Some(&dir.to_string_lossy()));
+                move_list.append(&btn);
+                btn
+            })
+            .collect();
+        container.append(&move_list);
+
+        let undo_bar = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        undo_bar.add_css_class("menu-undo");
+        undo_bar.set_visible(false);
+        let undo_label = gtk4::Label::new(None);
+        undo_label.add_css_class("menu-size");
+        undo_label.set_hexpand(true);
+        undo_label.set_xalign(0.0);
+        undo_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+        undo_bar.append(&undo_label);
+        let btn_undo = action_button("Undo", &keys.undo);
+        undo_bar.append(&btn_undo);
+        container.append(&undo_bar);
+
+        // every history entry; picking one swaps the preview in place, and
+        // ctrl-click adds it to the set the Drag button takes
+        list.set_selection_mode(gtk4::SelectionMode::Multiple);
+        list.set_activate_on_single_click(true);
+        let ctrl_click = gtk4::GestureClick::new();
+        ctrl_click.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        {
+            let list = list.clone();
+            ctrl_click.connect_pressed(move |g, _, _, y| {
+                if !g.current_event_state().contains(gdk::ModifierType::CONTROL_MASK) {
+                    return;
+                }
+                let Some(row) = list.row_at_y(y as i32) else { return };
+                if row.is_selected() {
+                    list.unselect_row(&row);
+                } else {
+                    list.select_row(Some(&row));
+                }
+                // toggling a row doesn't show it
+                g.set_state(gtk4::EventSequenceState::Claimed);
+            });
+        }
+        list.add_controller(ctrl_click);
+        for (i, item) in state.borrow().items.iter().enumerate() {
+            list.append(&history_row(i, item, &shared_cfg.cache_dir));
+        }
+        let count = state.borrow().items.len();
+        if count > 1 {
+            let scroller = gtk4::ScrolledWindow::new();
+            scroller.set_hscrollbar_policy(gtk4::PolicyType::Never);
+            scroller.set_propagate_natural_height(true);
+            scroller.set_max_content_height(HISTORY_MAX_H);
+            scroller.set_child(Some(&list));
+            let expander = gtk4::Expander::new(Some(&format!("History ({count})")));
+            expander.add_css_class("menu-history");
+            expander.set_child(Some(&scroller));
+            container.append(&expander);
+        }
+
+        // redraw everything that depends on the shown entry
+        let refresh: Rc<dyn Fn()> = {
+            let (state, list) = (state.clone(), list.clone());
+            let (preview, btn_edit, drag_all) = (preview.clone(), btn_edit.clone(), drag_all.clone());
+            let drag_all_label = drag_all_label.clone();
+            let (btn_rename, rename_field) = (btn_rename.clone(), rename_field.clone());
+            let move_list = move_list.clone();
+            let (btn_delete, armed) = (btn_delete.clone(), delete_armed.clone());
+            let delete_keys = keys.delete.clone();
+            let cfg = shared_cfg.clone();
+            Rc::new(move || {
+                let st = state.borrow();
+                let item = st.current();
+                fill_preview(&preview, item, limits, &cfg.cache_dir);
+                btn_edit.set_visible(!item.entry.is_batch());
+                btn_rename.set_visible(!item.entry.is_batch());
+                rename_field.set_visible(false);
+                move_list.set_visible(false);
+                if armed.replace(false) {
+                    set_hint(&btn_delete, "Delete", &delete_keys);
+                    btn_delete.remove_css_class("menu-danger");
+                }
+                let all = st.all_paths().len();
+                drag_all_label.set_label(&format!("All {all}"));
+                drag_all.set_visible(all > item.paths.len());
+                // showing an entry outside the dragged set starts a new one
+                let row = list.row_at_index(st.shown as i32);
+                if !row.as_ref().is_some_and(|r| r.is_selected()) {
+                    list.unselect_all();
+                    list.select_row(row.as_ref());
+                }
+            })
+        };
+        refresh();
+
+        let pick: Rc<dyn Fn(usize)> = {
+            let (state, refresh, state_file) = (state.clone(), refresh.clone(), state_file.clone());
+            Rc::new(move |i: usize| {
+                let entry = {
+                    let mut st = state.borrow_mut();
+                    if i == st.shown || st.items.get(i).is_none_or(|it| it.paths.is_empty()) {
+                        return;
+                    }
+                    st.shown = i;
+                    st.current().entry.clone()
+                };
+                refresh();
+                persist_selection(&state_file, signal, &entry);
+            })
+        };
+        let p = pick.clone();
+        list.connect_row_activated(move |_, row| p(row.index() as usize));
+
+        let step: Rc<dyn Fn(bool)> = {
+            let (state, pick) = (state.clone(), pick.clone());
+            Rc::new(move |older| {
+                let next = state.borrow().step(older);
+                if let Some(i) = next {
+                    pick(i);
+                }
+            })
+        };
+
+        let pin: Rc<dyn Fn()> = {
+            let (state, refresh, state_file) = (state.clone(), refresh.clone(), state_file.clone());
+            Rc::new(move || {
+                let entry = state.borrow().current().entry.clone();
+                let mut pinned = None;
+                if let Err(e) = with_history(&state_file, |h| pinned = h.toggle_pin(&entry)) {
+                    log::warn!("failed to pin: {e}"; path = entry.path.display());
+                }
+                if let Some(pinned) = pinned {
+                    let mut st = state.borrow_mut();
+                    let shown = st.shown;
+                    st.items[shown].entry.pinned = pinned;
+                    drop(st);
+                    refresh();
+                }
+            })
+        };
+
+        // an entry whose files went: grey it out and show another
+        let forget: Rc<dyn Fn(usize)> = {
+            let (state, list, pick, a) = (state.clone(), list.clone(), pick.clone(), app_handle.clone());
+            Rc::new(move |i: usize| {
+                state.borrow_mut().items[i].paths.clear();
+                if let Some(row) = list.row_at_index(i as i32) {
+                    list.unselect_row(&row);
+                    set_row_usable(&row, false);
+                }
+                let next = {
+                    let st = state.borrow();
+                    st.step(true).or_else(|| st.step(false))
+                };
+                match next {
+                    Some(n) => pick(n),
+                    None => a.quit(),
+                }
+            })
+        };
+
+        // the last trashing, and which item it was
+        let last_trashed: Rc<RefCell<Option<(usize, Undo)>>> = Rc::new(RefCell::new(None));
+        let trash: Rc<dyn Fn()> = {
+            let (state, forget, cfg) = (state.clone(), forget.clone(), shared_cfg.clone());
+            let refresh = refresh.clone();
+            let (last, undo_bar) = (last_trashed.clone(), undo_bar.clone());
+            let undo_label = undo_label.clone();
+            Rc::new(move || {
+                let (shown, entry) = {
+                    let st = state.borrow();
+                    (st.shown, st.current().entry.clone())
+                };
+                let done = match trash::trash_entry(&cfg, &entry) {
+                    Ok(done) => done,
+                    Err(e) => {
+                        log::warn!("failed to trash: {e:#}"; path = entry.path.display());
+                        return;
+                    }
+                };
+                trash::remember(&cfg, &done);
+                undo_label.set_text(&format!("Trashed {}", entry.name));
+                undo_bar.set_visible(true);
+                *last.borrow_mut() = Some((shown, done));
+                // some files may have stayed behind
+                let left = existing_paths(&entry.paths());
+                if left.is_empty() {
+                    forget(shown);
+                } else {
+                    state.borrow_mut().items[shown].paths = left;
+                    refresh();
+                }
+            })
+        };
+        let undo: Rc<dyn Fn()> = {
+            let (state, list, pick, cfg) = (state.clone(), list.clone(), pick.clone(), shared_cfg.clone());
+            let (last, undo_bar) = (last_trashed.clone(), undo_bar.clone());
+            Rc::new(move || {
+                let Some((i, done)) = last.borrow_mut().take() else {
+                    return;
+                };
+                undo_bar.set_visible(false);
+                match trash::undo(&cfg, &done) {
+                    Ok(()) => remove_stale(&cfg.trash_undo_file()),
+                    Err(e) => log::warn!("failed to undo: {e:#}"),
+                }
+                // whatever made it back
+                let back = {
+                    let mut st = state.borrow_mut();
+                    let item = &mut st.items[i];
+                    item.paths = existing_paths(&item.entry.paths());
+                    !item.paths.is_empty()
+                };
+                if back {
+                    if let Some(row) = list.row_at_index(i as i32) {
+                        set_row_usable(&row, true);
+                    }
+                    pick(i);
+                }
+            })
+        };
+
+        let delete: Rc<dyn Fn()> = {
+            let (state, forget, cfg) = (state.clone(), forget.clone(), shared_cfg.clone());
+            let (btn, armed) = (btn_delete.clone(), delete_armed.clone());
+            Rc::new(move || {
+                if !armed.replace(true) {
+                    set_hint(&btn, "Really delete?", &[]);
+                    btn.add_css_class("menu-danger");
+                    return;
+                }
+                let (shown, entry) = {
+                    let st = state.borrow();
+                    (st.shown, st.current().entry.clone())
+                };
+                match delete_entry(&cfg, &entry) {
+                    Ok(_) => forget(shown),
+                    Err(e) => log::warn!("failed to delete: {e:#}"; path = entry.path.display()),
+                }
+            })
+        };
+
+        let start_rename: Rc<dyn Fn()> = {
+            let (state, field) = (state.clone(), rename_field.clone());
+            Rc::new(move || {
+                let st = state.borrow();
+                let entry = &st.current().entry;
+                if entry.is_batch() {
+                    return;
+                }
+                field.set_text(&entry.name);
+                field.remove_css_class("error");
+                field.set_visible(true);
+                field.grab_focus();
+                // select the stem, so typing keeps the extension
+                let stem = Path::new(&entry.name)
+                    .file_stem()
+                    .map_or(0, |s| s.to_string_lossy().chars().count());
+                field.select_region(0, stem as i32);
+            })
+        };
+        {
+            let (state, list, refresh) = (state.clone(), list.clone(), refresh.clone());
+            let cfg = shared_cfg.clone();
+            rename_field.connect_activate(move |field| {
+                let (shown, entry) = {
+                    let st = state.borrow();
+                    (st.shown, st.current().entry.clone())
+                };
+                let to = match rename_entry(&cfg, &entry, field.text().trim()) {
+                    Ok(to) => to,
+                    Err(e) => {
+                        field.add_css_class("error");
+                        field.set_tooltip_text(Some(&format!("{e:#}")));
+                        return;
+                    }
+                };
+                {
+                    let mut st = state.borrow_mut();
+                    let item = &mut st.items[shown];
+                    item.entry.name = to.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    item.entry.path = to.clone();
+                    item.paths = vec![to];
+                    if let Some(row) = list.row_at_index(shown as i32) {
+                        list.remove(&row);
+                    }
+                    list.insert(&history_row(shown, item, &cfg.cache_dir), shown as i32);
+                }
+                refresh();
+            });
+            rename_field.connect_changed(|field| {
+                field.remove_css_class("error");
+                field.set_tooltip_text(None);
+            });
+        }
+
+        let move_to: Rc<dyn Fn(usize)> = {
+            let (state, move_list, a) = (state.clone(), move_list.clone(), app_handle.clone());
+            let (cfg, dests) = (shared_cfg.clone(), dests.clone());
+            Rc::new(move |i: usize| {
+                let Some(dir) = dests.get(i) else {
+                    return;
+                };
+                let entry = state.borrow().current().entry.clone();
+                match move_entry(&cfg, &entry, dir) {
+                    Ok(_) => a.quit(),
+                    Err(e) => {
+                        log::warn!("failed to move: {e:#}"; path = entry.path.display());
+                        move_list.set_visible(false);
+                    }
+                }
+            })
+        };
+        let toggle_move: Rc<dyn Fn()> = {
+            let move_list = move_list.clone();
+            Rc::new(move || move_list.set_visible(!move_list.is_visible()))
+        };
+        for (i, btn) in dest_buttons.iter().enumerate() {
+            let f = move_to.clone();
+            btn.connect_clicked(move |_| f(i));
+        }
+
+        for (btn, f) in [
+            (&btn_rename, start_rename.clone()),
+            (&btn_move, toggle_move.clone()),
+            (&btn_trash, trash.clone()),
+            (&btn_delete, delete.clone()),
+            (&btn_undo, undo.clone()),
+        ] {
+            btn.connect_clicked(move |_| f());
+        }
+
         // close button at top-right
         let header = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
         let spacer = gtk4::Label::new(None);
@@ -388,53 +1174,153 @@ pub fn run(cfg: &Config) -> Result<()> {
         outer.append(&header);
         outer.append(&container);
 
+        // thin countdown bar for the auto-dismiss timer
+        let progress = gtk4::ProgressBar::new();
+        progress.add_css_class("menu-progress");
+        progress.set_fraction(1.0);
+        if menu_dismiss > 0 {
+            outer.append(&progress);
+        }
+
         win.set_child(Some(&outer));
         win.present();
 
-        // scroll to cycle through history
+        // scroll to cycle through history: down is older, up is newer
         let scroll_ctl = gtk4::EventControllerScroll::new(
-            gtk4::EventControllerScrollFlags::VERTICAL,
+            gtk4::EventControllerScrollFlags::VERTICAL | gtk4::EventControllerScrollFlags::DISCRETE,
         );
-        let a = app_handle.clone();
+        let s = step.clone();
         scroll_ctl.connect_scroll(move |_, _, dy| {
-            let dir = if dy > 0.0 { "down" } else { "up" };
-            // update history selection (non-blocking spawn, not .output())
-            let bin = std::env::current_exe().unwrap_or_else(|_| "glance".into());
-            let _ = Command::new(&bin).args(["scroll", dir]).spawn();
-            // relaunch menu with new selection after a short delay
-            let bin2 = std::env::current_exe().unwrap_or_else(|_| "glance".into());
-            glib::timeout_add_local_once(Duration::from_millis(100), move || {
-                let _ = Command::new(&bin2).arg("menu").spawn();
-            });
-            a.quit();
+            s(dy > 0.0);
             glib::Propagation::Stop
         });
         win.add_controller(scroll_ctl);
 
-        // escape to dismiss
+        // keyboard shortcuts; captured before the focused button or list
+        // sees them, so arrows always move through history
+        let bindings = key_bindings(&keys);
         let key_ctl = gtk4::EventControllerKey::new();
+        key_ctl.set_propagation_phase(gtk4::PropagationPhase::Capture);
         let a = app_handle.clone();
+        let field = rename_field.clone();
+        let move_list = move_list.clone();
         key_ctl.connect_key_pressed(move |_, keyval, _, _| {
-            if keyval == gdk::Key::Escape {
-                a.quit();
-                glib::Propagation::Stop
-            } else {
-                glib::Propagation::Proceed
+            let key = keyval.to_lower();
+            // while renaming, keys are for the text field; Escape gives up
+            if WidgetExt::is_visible(&field) {
+                if key == gdk::Key::Escape {
+                    field.set_visible(false);
+                    return glib::Propagation::Stop;
+                }
+                return glib::Propagation::Proceed;
             }
+            // with the destinations open, number keys pick one of them
+            if move_list.is_visible() {
+                if key == gdk::Key::Escape {
+                    move_list.set_visible(false);
+                    return glib::Propagation::Stop;
+                }
+                if let Some(d) = key.to_unicode().and_then(|c| c.to_digit(10)).filter(|&d| d > 0) {
+                    move_to(d as usize - 1);
+                    return glib::Propagation::Stop;
+                }
+            }
+            let Some(&(_, action)) = bindings.iter().find(|(k, _)| *k == key) else {
+                // number keys jump straight to that index
+                return match key.to_unicode().and_then(|c| c.to_digit(10)) {
+                    Some(d) => {
+                        pick(d as usize);
+                        glib::Propagation::Stop
+                    }
+                    None => glib::Propagation::Proceed,
+                };
+            };
+            match action {
+                KeyAction::Older => step(true),
+                KeyAction::Newer => step(false),
+                KeyAction::Open if has_open => open(),
+                KeyAction::Copy if has_copy => copy(),
+                KeyAction::Edit if has_edit => edit(),
+                KeyAction::Drag if has_drag => drag_key(),
+                KeyAction::Pin => pin(),
+                KeyAction::Rename if has_rename => start_rename(),
+                KeyAction::Move if has_move => toggle_move(),
+                KeyAction::Trash if has_trash => trash(),
+                KeyAction::Delete if has_delete => delete(),
+                KeyAction::Undo => undo(),
+                KeyAction::Close => a.quit(),
+                _ => return glib::Propagation::Proceed,
+            }
+            glib::Propagation::Stop
         });
         win.add_controller(key_ctl);
 
-        // auto-dismiss
+        // auto-dismiss, paused while the pointer is over the menu
         if menu_dismiss > 0 {
+            let hovered = Rc::new(Cell::new(false));
+            let motion = gtk4::EventControllerMotion::new();
+            let h = hovered.clone();
+            motion.connect_enter(move |_, _, _| h.set(true));
+            let h = hovered.clone();
+            motion.connect_leave(move |_| h.set(false));
+            win.add_controller(motion);
+
+            let total = menu_dismiss as f64;
+            let remaining = Cell::new(total);
+            let tick = Duration::from_millis(PROGRESS_TICK_MS);
             let a = app_handle.clone();
-            glib::timeout_add_local_once(Duration::from_secs(menu_dismiss), move || {
-                a.quit();
+            let field = rename_field.clone();
+            glib::timeout_add_local(tick, move || {
+                if !hovered.get() && !WidgetExt::is_visible(&field) {
+                    remaining.set(remaining.get() - tick.as_secs_f64());
+                }
+                if remaining.get() <= 0.0 {
+                    a.quit();
+                    return glib::ControlFlow::Break;
+                }
+                progress.set_fraction(remaining.get() / total);
+                glib::ControlFlow::Continue
             });
         }
     });
 
     app.run_with_args::<&str>(&[]);
-    let _ = std::fs::remove_file(menu_pid_path());
-    let _ = std::fs::remove_file(menu_lock_path());
+    remove_stale(&cfg.menu_pid_file());
+    remove_stale(&cfg.menu_lock_file());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn hints_underline_a_letter_or_append_the_key() {
+        assert_eq!(key_hint("Copy", &keys(&["c", "y"])), "<u>C</u>opy");
+        assert_eq!(key_hint("Edit", &keys(&["i"])), "Ed<u>i</u>t");
+        assert_eq!(key_hint("Open", &keys(&["Return"])), "Open <small>\u{23ce}</small>");
+        assert_eq!(key_hint("Drag", &keys(&["x"])), "Drag <small>x</small>");
+        assert_eq!(key_hint("Drag", &[]), "Drag");
+    }
+
+    #[test]
+    fn directories_under_home_are_shortened() {
+        let home = Path::new("/home/ann");
+        assert_eq!(dir_label(Path::new("/home/ann/Documents/invoices"), home), "~/Documents/invoices");
+        assert_eq!(dir_label(home, home), "~");
+        assert_eq!(dir_label(Path::new("/home/annie"), home), "/home/annie");
+        assert_eq!(dir_label(Path::new("/mnt/usb"), Path::new("")), "/mnt/usb");
+    }
+
+    #[test]
+    fn long_names_are_cut_on_characters() {
+        assert_eq!(truncate_name("short.png", 24), "short.png");
+        let cut = truncate_name("スクリーンショット 2024-05-01 12-00-00.png", 24);
+        assert_eq!(cut.chars().count(), 24);
+        assert!(cut.ends_with('\u{2026}'));
+    }
+}
@@ -0,0 +1,287 @@
+//! Moving an entry's files to another directory, for `glance move` and the
+//! menu's "Move to…" list of favourite and recent destinations, and copying
+//! them there for `glance copy --to`.
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::log;
+use crate::select::Selector;
+use crate::state::{read_history, with_history, FileState};
+use crate::util::{existing_paths, remove_stale, signal_waybar};
+use crate::watch::expect_arrivals;
+use anyhow::Result;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How many recent destinations are remembered.
+const RECENT_MAX: usize = 5;
+
+/// `name` in `dir`, or `name (2)`, `name (3)`… if that's taken.
+pub fn free_name(dir: &Path, name: &str) -> PathBuf {
+    let first = dir.join(name);
+    if !first.exists() {
+        return first;
+    }
+    let (stem, ext) = match name.rfind('.').filter(|&i| i > 0) {
+        Some(i) => name.split_at(i),
+        None => (name, ""),
+    };
+    (2..)
+        .map(|n| dir.join(format!("{stem} ({n}){ext}")))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// Copy a file, keeping its age so it isn't taken for a new download.
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    // written under a dotfile name, so a watcher there doesn't see it half done
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+    let part = to.with_file_name(format!(".glance-move-{name}"));
+    let copied = fs::copy(from, &part).and_then(|_| {
+        let mtime = fs::metadata(from)?.modified()?;
+        File::options().write(true).open(&part)?.set_modified(mtime)?;
+        fs::rename(&part, to)
+    });
+    if copied.is_err() {
+        remove_stale(&part);
+    }
+    copied
+}
+
+/// What `rename` can't do across filesystems: copy, then remove the original.
+fn copy_across(from: &Path, to: &Path) -> io::Result<()> {
+    copy_file(from, to)?;
+    fs::remove_file(from)
+}
+
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => copy_across(from, to),
+        moved => moved,
+    }
+}
+
+fn read_recent(cfg: &Config) -> Vec<PathBuf> {
+    fs::read_to_string(cfg.recent_dirs_file())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Put `dir` at the front of the recent destinations.
+fn remember(cfg: &Config, dir: &Path) {
+    let mut recent = read_recent(cfg);
+    recent.retain(|d| d != dir);
+    recent.insert(0, dir.to_path_buf());
+    recent.truncate(RECENT_MAX);
+    let path = cfg.recent_dirs_file();
+    let saved = serde_json::to_string(&recent)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| {
+            fs::create_dir_all(&cfg.state_dir)?;
+            Ok(fs::write(&path, json)?)
+        });
+    if let Err(e) = saved {
+        log::warn!("failed to save recent destinations: {e}"; path = path.display());
+    }
+}
+
+/// Where "Move to…" offers to put files: the favourites, then recent
+/// destinations that aren't among them. Only directories that exist.
+pub fn destinations(cfg: &Config) -> Vec<PathBuf> {
+    let mut out: Vec<PathBuf> = Vec::new();
+    let favorites = cfg.favorite_dirs.iter().map(PathBuf::from);
+    for dir in favorites.chain(read_recent(cfg)) {
+        if dir.is_dir() && !out.contains(&dir) {
+            out.push(dir);
+        }
+    }
+    out
+}
+
+/// Move an entry's files into `dir` and follow them in history. Returns
+/// where they ended up.
+pub fn move_entry(cfg: &Config, entry: &FileState, dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        anyhow::bail!("{} is not a directory", dir.display());
+    }
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let paths = existing_paths(&entry.paths());
+    if paths.is_empty() {
+        anyhow::bail!("{} is gone", entry.name);
+    }
+    // both sides canonical, or a symlinked path to the same dir looks elsewhere
+    let already_there = |from: &Path| {
+        from.parent()
+            .and_then(|p| p.canonicalize().ok())
+            .is_some_and(|p| p == dir)
+    };
+    let plan: Vec<(PathBuf, PathBuf)> = paths
+        .iter()
+        .filter(|from| !already_there(from))
+        .map(|from| {
+            let name = from.file_name().unwrap_or_default().to_string_lossy();
+            (from.clone(), free_name(&dir, &name))
+        })
+        .collect();
+    if plan.is_empty() {
+        anyhow::bail!("{} is already in {}", entry.name, dir.display());
+    }
+
+    // a watcher on `dir` follows the files there instead of announcing them
+    let arriving: Vec<PathBuf> = plan.iter().map(|(_, to)| to.clone()).collect();
+    expect_arrivals(cfg, &arriving);
+    let follow = |moves: &[(PathBuf, PathBuf)], batch_dir: &Path| {
+        with_history(&cfg.state_file(), |h| {
+            for (from, to) in moves {
+                h.rename_path(from, to);
+            }
+            if entry.is_batch() {
+                let batch = h.entries.iter_mut().find(|e| {
+                    e.is_batch() && e.files.iter().any(|f| moves.iter().any(|(_, to)| to == f))
+                });
+                if let Some(e) = batch {
+                    e.path = batch_dir.to_path_buf();
+                }
+            }
+        })
+    };
+    follow(&plan, &dir)?;
+    let mut failed = Vec::new();
+    for (from, to) in &plan {
+        if let Err(e) = move_file(from, to) {
+            log::warn!("can't move {}: {e}", from.display());
+            failed.push((to.clone(), from.clone()));
+        }
+    }
+    if !failed.is_empty() {
+        // a batch stays where it was unless something actually went
+        let stays = if failed.len() == plan.len() { &entry.path } else { &dir };
+        follow(&failed, stays)?;
+        if failed.len() == plan.len() {
+            anyhow::bail!("can't move {} to {}", entry.name, dir.display());
+        }
+    }
+    let moved: Vec<PathBuf> = plan
+        .into_iter()
+        .map(|(_, to)| to)
+        .filter(|to| !failed.iter().any(|(t, _)| t == to))
+        .collect();
+    remember(cfg, &dir);
+    signal_waybar(cfg.signal_number);
+    log::info!("moved"; path = entry.path.display(), to = dir.display(), count = moved.len());
+    Ok(moved)
+}
+
+/// Copy an entry's files into `dir`, leaving the entry where it is. Returns
+/// the copies.
+pub fn copy_entry(cfg: &Config, entry: &FileState, dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        anyhow::bail!("{} is not a directory", dir.display());
+    }
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let paths = existing_paths(&entry.paths());
+    if paths.is_empty() {
+        anyhow::bail!("{} is gone", entry.name);
+    }
+    let plan: Vec<(PathBuf, PathBuf)> = paths
+        .iter()
+        .map(|from| {
+            let name = from.file_name().unwrap_or_default().to_string_lossy();
+            (from.clone(), free_name(&dir, &name))
+        })
+        .collect();
+    // a copy glance made on request isn't a new arrival
+    let arriving: Vec<PathBuf> = plan.iter().map(|(_, to)| to.clone()).collect();
+    expect_arrivals(cfg, &arriving);
+    let mut copied = Vec::new();
+    for (from, to) in plan {
+        match copy_file(&from, &to) {
+            Ok(()) => copied.push(to),
+            Err(e) => log::warn!("can't copy {}: {e}", from.display()),
+        }
+    }
+    if copied.is_empty() {
+        anyhow::bail!("can't copy {} to {}", entry.name, dir.display());
+    }
+    remember(cfg, &dir);
+    log::info!("copied"; path = entry.path.display(), to = dir.display(), count = copied.len());
+    Ok(copied)
+}
+
+pub fn run_copy(cfg: &Config, to: &Path, sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let Some(entry) = sel.target(&history, SystemClock.now(), cfg)? else {
+        return Ok(());
+    };
+    for path in copy_entry(cfg, entry, to)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+pub fn run(cfg: &Config, to: &Path, sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let Some(entry) = sel.target(&history, SystemClock.now(), cfg)? else {
+        return Ok(());
+    };
+    for path in move_entry(cfg, entry, to)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taken_names_get_a_number() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        assert_eq!(free_name(d, "shot.png"), d.join("shot.png"));
+        fs::write(d.join("shot.png"), "").unwrap();
+        fs::write(d.join("shot (2).png"), "").unwrap();
+        assert_eq!(free_name(d, "shot.png"), d.join("shot (3).png"));
+        fs::write(d.join("README"), "").unwrap();
+        assert_eq!(free_name(d, "README"), d.join("README (2)"));
+    }
+
+    #[test]
+    fn copies_keep_contents_and_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("a.pdf"), dir.path().join("b.pdf"));
+        fs::write(&from, "pdf").unwrap();
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        File::options().write(true).open(&from).unwrap().set_modified(old).unwrap();
+
+        copy_across(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "pdf");
+        assert_eq!(fs::metadata(&to).unwrap().modified().unwrap(), old);
+        assert!(!dir.path().join(".glance-move-b.pdf").exists());
+    }
+
+    #[test]
+    fn favorites_come_before_recent_destinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = Config {
+            state_dir: dir.path().join("state"),
+            favorite_dirs: vec![dir.path().join("fav").to_string_lossy().into_owned()],
+            ..Config::default()
+        };
+        for d in ["fav", "one", "two"] {
+            fs::create_dir(dir.path().join(d)).unwrap();
+        }
+        remember(&cfg, &dir.path().join("one"));
+        remember(&cfg, &dir.path().join("gone"));
+        remember(&cfg, &dir.path().join("two"));
+        remember(&cfg, &dir.path().join("fav"));
+        remember(&cfg, &dir.path().join("one"));
+        assert_eq!(
+            destinations(&cfg),
+            ["fav", "one", "two"].map(|d| dir.path().join(d))
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::list::age;
+use crate::menu::resolve_editor;
+use crate::runner;
+use crate::select::Selector;
+use crate::state::{read_history, FileState, HistoryState};
+use crate::util::{existing_paths, human_size, is_image, print_stdout};
+use crate::{copy, drag, log, menu, thumbnail};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use gtk4::glib;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Launchers tried, in order, when `picker = "auto"`.
+const LAUNCHERS: &[&[&str]] = &[
+    &["rofi", "-dmenu", "-i", "-p", "glance", "-show-icons"],
+    &["fuzzel", "--dmenu", "--prompt", "glance>"],
+    &["wofi", "--dmenu", "-i", "-p", "glance"],
+    &["dmenu", "-i", "-p", "glance"],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Action {
+    Copy,
+    Open,
+    Drag,
+    Edit,
+    Menu,
+    /// Print the chosen path(s) to stdout
+    Print,
+}
+
+/// The launcher command line: the configured one, split as a shell would,
+/// or the first installed.
+fn launcher(cfg: &Config) -> Result<Vec<String>> {
+    if cfg.picker != "auto" {
+        let argv = glib::shell_parse_argv(&cfg.picker)
+            .with_context(|| format!("can't parse picker {:?}", cfg.picker))?;
+        return Ok(argv.iter().map(|a| a.to_string_lossy().into_owned()).collect());
+    }
+    let argv = LAUNCHERS
+        .iter()
+        .find(|l| runner::installed(l[0]))
+        .ok_or_else(|| anyhow::anyhow!("no launcher found; install rofi, fuzzel, wofi or dmenu"))?;
+    Ok(argv.iter().map(|a| a.to_string()).collect())
+}
+
+/// rofi and fuzzel take an icon per line as `text\0icon\x1fNAME-OR-PATH`.
+fn supports_icons(bin: &str) -> bool {
+    let name = Path::new(bin).file_name().unwrap_or_default();
+    name == "rofi" || name == "fuzzel"
+}
+
+fn icon(e: &FileState, cache: &Path) -> String {
+    if e.is_batch() {
+        "folder".into()
+    } else if let Some(thumb) = thumbnail::lookup(cache, &e.path, thumbnail::Size::Normal) {
+        thumb.to_string_lossy().into_owned()
+    } else if is_image(&e.path) && e.path.exists() {
+        e.path.to_string_lossy().into_owned()
+    } else {
+        "text-x-generic".into()
+    }
+}
+
+/// One line per entry, led by its index so any launcher's choice can be
+/// mapped back. `icons` is the cache to find thumbnails in, if the launcher
+/// shows them.
+fn menu_lines(history: &HistoryState, now: f64, icons: Option<&Path>) -> String {
+    let mut out = String::new();
+    for (i, e) in history.entries.iter().enumerate() {
+        let mut line = format!("{i}  {}  ({}, {} ago)", e.name, human_size(e.size), age(now, e.time));
+        if e.missing {
+            line.push_str("  [missing]");
+        }
+        if let Some(cache) = icons {
+            line.push_str(&format!("\0icon\x1f{}", icon(e, cache)));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// The index at the start of the line the launcher printed.
+fn parse_choice(stdout: &[u8]) -> Option<usize> {
+    let text = String::from_utf8_lossy(stdout);
+    let line = text.lines().next()?.trim_start();
+    line.split_whitespace().next()?.parse().ok()
+}
+
+fn run_custom(cmd: &str, paths: &[PathBuf]) {
+    let mut parts = cmd.split_whitespace();
+    let Some(bin) = parts.next() else { return };
+    runner::launch(Command::new(bin).args(parts).args(paths));
+}
+
+/// Show the history in a dmenu-style launcher and run `action` (or the
+/// `exec` command, with the chosen paths appended) on the pick.
+pub fn run(cfg: &Config, action: Action, exec: Option<&str>) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    if history.entries.is_empty() {
+        return Ok(());
+    }
+    let cmd = launcher(cfg)?;
+    let icons = supports_icons(&cmd[0]).then_some(cfg.cache_dir.as_path());
+    let input = menu_lines(&history, SystemClock.now(), icons);
+    let out = runner::output_with_stdin(Command::new(&cmd[0]).args(&cmd[1..]), input.as_bytes())?;
+    // launchers exit non-zero when dismissed with Escape
+    let Some(i) = parse_choice(&out.stdout).filter(|&i| i < history.entries.len()) else {
+        log::debug!("nothing picked"; launcher = cmd[0]);
+        return Ok(());
+    };
+    let sel = Selector::index(i);
+    let paths = existing_paths(&history.entries[i].paths());
+
+    if let Some(exec) = exec {
+        run_custom(exec, &paths);
+        return Ok(());
+    }
+    match action {
+        Action::Copy => copy::run(cfg, &sel),
+        Action::Drag => drag::run(cfg, false, &[], &sel),
+        Action::Menu => menu::run(cfg, &sel),
+        Action::Open => {
+            // a batch opens its folder
+            runner::launch(Command::new("xdg-open").arg(&history.entries[i].path));
+            Ok(())
+        }
+        Action::Edit => {
+            let (bin, args) = resolve_editor(&cfg.editor);
+            runner::launch(Command::new(bin).args(args).args(&paths));
+            Ok(())
+        }
+        Action::Print => {
+            let text: String = paths.iter().map(|p| format!("{}\n", p.display())).collect();
+            print_stdout(&text)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> HistoryState {
+        HistoryState {
+            entries: vec![
+                FileState {
+                    path: "/nonexistent/shot.png".into(),
+                    name: "shot.png".into(),
+                    size: 2048,
+                    time: 940.0,
+                    ..Default::default()
+                },
+                FileState {
+                    path: "/nonexistent/dl".into(),
+                    name: "2 files in dl".into(),
+                    files: vec!["/nonexistent/dl/a".into(), "/nonexistent/dl/b".into()],
+                    time: 0.0,
+                    missing: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lines_lead_with_the_index() {
+        assert_eq!(
+            menu_lines(&history(), 1000.0, None),
+            "0  shot.png  (2.0 KB, 1m ago)\n1  2 files in dl  (0 B, 16m ago)  [missing]\n"
+        );
+    }
+
+    #[test]
+    fn icons_use_rofi_syntax() {
+        let lines = menu_lines(&history(), 1000.0, Some(Path::new("/nonexistent")));
+        let second = lines.lines().nth(1).unwrap();
+        assert!(second.ends_with("[missing]\0icon\x1ffolder"));
+        // a missing image can't be its own thumbnail
+        assert!(lines.starts_with("0  shot.png  (2.0 KB, 1m ago)\0icon\x1ftext-x-generic\n"));
+    }
+
+    #[test]
+    fn choice_is_read_back_from_the_index() {
+        assert_eq!(parse_choice(b"3  notes.txt  (1 B, 2s ago)\n"), Some(3));
+        assert_eq!(parse_choice(b""), None);
+        assert_eq!(parse_choice(b"typed something\n"), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! Text previews for the menu: the first lines of small text and source
+//! files, with CSV/TSV laid out as a table and JSON pretty-printed.
+
+use crate::config::Config;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const TEXT_EXTS: &[&str] = &[
+    "txt", "md", "log", "json", "csv", "tsv", "toml", "yaml", "yml", "ini", "conf", "cfg", "xml",
+    "html", "css", "js", "ts", "rs", "py", "sh", "c", "h", "cpp", "hpp", "go", "java", "kt", "rb",
+    "lua", "sql", "diff", "patch", "nix",
+];
+/// Longest line shown before it is cut off: what fits across the menu in
+/// its 10px monospace.
+const LINE_CHARS: usize = 30;
+const TABLE_COLUMNS: usize = 6;
+const CELL_CHARS: usize = 14;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Preview {
+    /// Lines of text, already clipped
+    Text(String),
+    /// Rows of cells, already clipped
+    Table(Vec<Vec<String>>),
+}
+
+/// How much of a file a preview may show.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub lines: usize,
+    pub bytes: u64,
+}
+
+impl Limits {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self { lines: cfg.text_preview_lines, bytes: cfg.text_preview_bytes }
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default()
+}
+
+pub fn is_text(path: &Path) -> bool {
+    TEXT_EXTS.contains(&extension(path).as_str())
+}
+
+/// The bytes as text, or None if they look binary. The sample may end
+/// part-way through a character.
+fn sniff(bytes: &[u8]) -> Option<&str> {
+    if bytes.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+/// `text` cut to `max` characters with an ellipsis.
+fn clip(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        let head: String = text.chars().take(max - 1).collect();
+        format!("{head}\u{2026}")
+    } else {
+        text.to_string()
+    }
+}
+
+fn head_lines(text: &str, lines: usize) -> String {
+    text.lines()
+        .take(lines)
+        .map(|l| clip(&l.replace('\t', "    "), LINE_CHARS))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One CSV record per line, honouring double-quoted fields (but not
+/// newlines inside them).
+fn parse_table(text: &str, sep: char, rows: usize) -> Vec<Vec<String>> {
+    let mut out = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()).take(rows) {
+        let mut cells = Vec::new();
+        let (mut cell, mut quoted) = (String::new(), false);
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                }
+                '"' => quoted = !quoted,
+                c if c == sep && !quoted => cells.push(std::mem::take(&mut cell)),
+                c => cell.push(c),
+            }
+        }
+        cells.push(cell);
+        out.push(cells.iter().take(TABLE_COLUMNS).map(|c| clip(c.trim(), CELL_CHARS)).collect());
+    }
+    out
+}
+
+/// A preview of the file's contents, or None if it isn't a text file we
+/// know, is binary, or previews are turned off.
+pub fn load(path: &Path, limits: Limits) -> Option<Preview> {
+    if limits.lines == 0 || !is_text(path) {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    File::open(path).ok()?.take(limits.bytes).read_to_end(&mut bytes).ok()?;
+    let complete = std::fs::metadata(path).ok()?.len() <= limits.bytes;
+    Some(render(&extension(path), sniff(&bytes)?, complete, limits.lines))
+}
+
+fn render(ext: &str, text: &str, complete: bool, lines: usize) -> Preview {
+    match ext {
+        "csv" => Preview::Table(parse_table(text, ',', lines)),
+        "tsv" => Preview::Table(parse_table(text, '\t', lines)),
+        // only a whole document can be re-indented
+        "json" if complete => {
+            let pretty = serde_json::from_str::<serde_json::Value>(text)
+                .ok()
+                .and_then(|v| serde_json::to_string_pretty(&v).ok());
+            Preview::Text(head_lines(pretty.as_deref().unwrap_or(text), lines))
+        }
+        _ => Preview::Text(head_lines(text, lines)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn binary_content_is_rejected() {
+        assert_eq!(sniff(b"plain text\n"), Some("plain text\n"));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0"), None);
+        assert_eq!(sniff(b"caf\xc3"), Some("caf"), "cut mid-character");
+        assert_eq!(sniff(b"\xff\xfe text"), None);
+    }
+
+    #[test]
+    fn text_is_cut_to_the_first_lines() {
+        let long = "x".repeat(100);
+        let text = format!("one\n\ttwo\n{long}\nfour\n");
+        let Preview::Text(shown) = render("txt", &text, true, 3) else { panic!() };
+        let lines: Vec<_> = shown.lines().collect();
+        assert_eq!(lines[..2], ["one", "    two"]);
+        assert_eq!(lines[2].chars().count(), LINE_CHARS);
+        assert!(lines[2].ends_with('\u{2026}'));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn json_is_pretty_printed_when_whole() {
+        let text = r#"{"a":[1,2]}"#;
+        assert_eq!(
+            render("json", text, true, 10),
+            Preview::Text("{\n  \"a\": [\n    1,\n    2\n  ]\n}".into())
+        );
+        // a truncated document is shown as it is
+        assert_eq!(render("json", text, false, 10), Preview::Text(text.into()));
+    }
+
+    #[test]
+    fn csv_becomes_a_table() {
+        let text = "name,note\n\"Doe, J\",\"says \"\"hi\"\"\"\n\nx,y\nz,w\n";
+        assert_eq!(
+            render("csv", text, true, 3),
+            Preview::Table(vec![
+                vec!["name".into(), "note".into()],
+                vec!["Doe, J".into(), "says \"hi\"".into()],
+                vec!["x".into(), "y".into()],
+            ])
+        );
+        let Preview::Table(rows) = render("tsv", "a\tb\tc\td\te\tf\tg\n", true, 5) else { panic!() };
+        assert_eq!(rows[0].len(), TABLE_COLUMNS);
+    }
+
+    #[test]
+    fn loads_respect_the_byte_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        let mut f = File::create(&path).unwrap();
+        write!(f, "first\nsecond\nthird\n").unwrap();
+        let limits = Limits { lines: 10, bytes: 8 };
+        assert_eq!(load(&path, limits), Some(Preview::Text("first\nse".into())));
+        assert_eq!(load(&path, Limits { lines: 0, ..limits }), None);
+        assert_eq!(load(&dir.path().join("shot.png"), limits), None);
+    }
+}
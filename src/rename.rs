@@ -0,0 +1,65 @@
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::log;
+use crate::select::Selector;
+use crate::state::{read_history, with_history, FileState};
+use crate::util::signal_waybar;
+use crate::watch::expect_arrivals;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// A new name must stay a plain file name in the same directory.
+pub fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        anyhow::bail!("{name:?} is not a valid file name");
+    }
+    Ok(())
+}
+
+/// Rename an entry's file within its directory and follow it in history.
+pub fn rename_entry(cfg: &Config, entry: &FileState, new_name: &str) -> Result<PathBuf> {
+    check_name(new_name)?;
+    if entry.is_batch() {
+        anyhow::bail!("can't rename a batch of files");
+    }
+    let from = &entry.path;
+    let to = from.with_file_name(new_name);
+    if to == *from {
+        return Ok(to);
+    }
+    if to.exists() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+    expect_arrivals(cfg, std::slice::from_ref(&to));
+    std::fs::rename(from, &to).with_context(|| format!("can't rename {}", from.display()))?;
+    // the watcher may have beaten us to it; either way history follows
+    with_history(&cfg.state_file(), |h| {
+        h.rename_path(from, &to);
+    })?;
+    signal_waybar(cfg.signal_number);
+    log::info!("renamed"; from = from.display(), to = to.display());
+    Ok(to)
+}
+
+pub fn run(cfg: &Config, new_name: &str, sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let Some(entry) = sel.target(&history, SystemClock.now(), cfg)? else {
+        return Ok(());
+    };
+    let to = rename_entry(cfg, entry, new_name)?;
+    println!("{}", to.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_must_stay_in_the_directory() {
+        assert!(check_name("notes v2.txt").is_ok());
+        for bad in ["", ".", "..", "../up.png", "sub/dir.png"] {
+            assert!(check_name(bad).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! `[[rules]]`: acting on new files as they arrive, e.g. filing invoices
+//! from Downloads into a folder, or quietly trashing old screenshots.
+//!
+//! The watcher calls [`prepare`] for each file it would announce; the first
+//! rule whose conditions all hold decides what happens to it. Rules with a
+//! `min_age_days` get a second chance through [`prepare_aged`], as the
+//! watcher looks over history for files that have since come of age.
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::{Config, Rule};
+use crate::log;
+use crate::move_to::{free_name, move_file};
+use crate::rename::check_name;
+use crate::thumbnail;
+use crate::trash;
+use crate::util::glob_match;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use gtk4::glib;
+use std::cell::OnceCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const DAY: f64 = 86400.0;
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum RulesCommand {
+    /// Show which rule a file would match and what it would do, without
+    /// doing any of it
+    Test { path: PathBuf },
+}
+
+/// What rules match on. The MIME type is only looked up if a rule asks.
+struct Facts<'a> {
+    path: &'a Path,
+    name: String,
+    size: u64,
+    mtime: f64,
+    age_days: f64,
+    mime: OnceCell<String>,
+}
+
+impl<'a> Facts<'a> {
+    fn new(path: &'a Path, now: f64) -> Option<Self> {
+        let meta = fs::metadata(path).ok().filter(|m| m.is_file())?;
+        let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs_f64();
+        Some(Self {
+            path,
+            name: path.file_name()?.to_string_lossy().into_owned(),
+            size: meta.len(),
+            mtime,
+            age_days: (now - mtime) / DAY,
+            mime: OnceCell::new(),
+        })
+    }
+
+    fn mime(&self) -> &str {
+        self.mime.get_or_init(|| thumbnail::mime_type(self.path))
+    }
+}
+
+fn expand_dir(dir: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(dir).as_ref())
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(x), Ok(y)) if x == y)
+}
+
+/// Why `rule` doesn't match, or None if it does. The MIME type is checked
+/// last, as the only condition that costs anything.
+fn mismatch(rule: &Rule, f: &Facts) -> Option<String> {
+    if let Some(dir) = &rule.dir {
+        if !f.path.parent().is_some_and(|p| same_dir(p, &expand_dir(dir))) {
+            return Some(format!("not in {dir}"));
+        }
+    }
+    if let Some(glob) = &rule.glob {
+        if !glob_match(glob, &f.name) {
+            return Some(format!("name doesn't match {glob:?}"));
+        }
+    }
+    if let Some(min) = rule.min_size.filter(|&min| f.size < min) {
+        return Some(format!("smaller than {min} bytes"));
+    }
+    if let Some(max) = rule.max_size.filter(|&max| f.size > max) {
+        return Some(format!("larger than {max} bytes"));
+    }
+    if let Some(min) = rule.min_age_days.filter(|&min| f.age_days < min) {
+        return Some(format!("less than {min} days old"));
+    }
+    if let Some(max) = rule.max_age_days.filter(|&max| f.age_days > max) {
+        return Some(format!("more than {max} days old"));
+    }
+    if let Some(mime) = &rule.mime {
+        if !glob_match(mime, f.mime()) {
+            return Some(format!("type is {}, not {mime}", f.mime()));
+        }
+    }
+    None
+}
+
+fn label(rule: &Rule, index: usize) -> String {
+    if rule.name.is_empty() {
+        format!("rule {}", index + 1)
+    } else {
+        rule.name.clone()
+    }
+}
+
+/// One thing a rule does to a file, in the order they happen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Rename(PathBuf),
+    Copy(PathBuf),
+    Move(PathBuf),
+    Run(Vec<String>),
+    Trash,
+}
+
+impl Step {
+    fn describe(&self) -> String {
+        match self {
+            Step::Rename(to) => format!("rename to {}", file_name(to)),
+            Step::Copy(to) => format!("copy to {}", to.display()),
+            Step::Move(to) => format!("move to {}", to.display()),
+            Step::Run(argv) => format!("run {} on it", argv.join(" ")),
+            Step::Trash => "move to the trash".into(),
+        }
+    }
+}
+
+/// The local calendar date of a Unix timestamp, e.g. `2024-05-01`.
+fn local_date(secs: f64) -> Result<String> {
+    let date = glib::DateTime::from_unix_local(secs as i64)?;
+    Ok(date.format("%Y-%m-%d")?.into())
+}
+
+/// A `rename` template filled in for `name`.
+fn fill_template(template: &str, name: &str, date: &str) -> String {
+    let (stem, ext) = match name.rfind('.').filter(|&i| i > 0) {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (name, ""),
+    };
+    template
+        .replace("{name}", name)
+        .replace("{stem}", stem)
+        .replace("{ext}", ext)
+        .replace("{date}", date)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Work out what `rule` would do to the file, without touching it.
+fn plan(rule: &Rule, f: &Facts) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut path = f.path.to_path_buf();
+    if let Some(template) = &rule.rename {
+        let date = if template.contains("{date}") { local_date(f.mtime)? } else { String::new() };
+        let name = fill_template(template, &f.name, &date);
+        check_name(&name)?;
+        if name != f.name {
+            path = free_name(f.path.parent().unwrap_or(Path::new("/")), &name);
+            steps.push(Step::Rename(path.clone()));
+        }
+    }
+    if let Some(dir) = &rule.copy {
+        steps.push(Step::Copy(free_name(&expand_dir(dir), &file_name(&path))));
+    }
+    if let Some(dir) = rule.move_to.as_ref().filter(|_| !rule.trash) {
+        path = free_name(&expand_dir(dir), &file_name(&path));
+        steps.push(Step::Move(path.clone()));
+    }
+    if rule.trash {
+        steps.push(Step::Trash);
+    }
+    // last, so the command sees the file where it ends up (in the trash too)
+    if let Some(cmd) = &rule.run {
+        let argv: Vec<String> = cmd.split_whitespace().map(String::from).collect();
+        if !argv.is_empty() {
+            steps.push(Step::Run(argv));
+        }
+    }
+    Ok(steps)
+}
+
+fn make_parent(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("can't create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Carry out `steps`, keeping `current` on wherever the file is. Returns
+/// false if it went to the trash. A `run` is left to the watcher.
+fn execute(steps: &[Step], current: &mut PathBuf) -> Result<bool> {
+    let mut kept = true;
+    for step in steps {
+        match step {
+            Step::Rename(to) => {
+                fs::rename(&*current, to)
+                    .with_context(|| format!("can't rename to {}", to.display()))?;
+                *current = to.clone();
+            }
+            Step::Copy(to) => {
+                make_parent(to)?;
+                fs::copy(&*current, to).with_context(|| format!("can't copy to {}", to.display()))?;
+            }
+            Step::Move(to) => {
+                make_parent(to)?;
+                move_file(current, to).with_context(|| format!("can't move to {}", to.display()))?;
+                *current = to.clone();
+            }
+            Step::Run(_) => {}
+            Step::Trash => {
+                *current = trash::trash(current)?.file;
+                kept = false;
+            }
+        }
+    }
+    Ok(kept)
+}
+
+/// A matching rule's plan for a file. Made on the watcher's loop, where it
+/// is cheap; [`Job::execute`] does the file work (a big copy to another disk
+/// can take a while) on the rules thread.
+#[derive(Debug, Clone)]
+pub struct Job {
+    rule: String,
+    path: PathBuf,
+    steps: Vec<Step>,
+    notify: bool,
+    tags: Vec<String>,
+    /// For a file already in history, rather than a new one
+    aged: bool,
+}
+
+impl Job {
+    /// Files the rule will create or move it to, which the watcher shouldn't
+    /// take for new arrivals
+    pub fn created(&self) -> Vec<PathBuf> {
+        self.steps
+            .iter()
+            .filter_map(|s| match s {
+                Step::Rename(p) | Step::Copy(p) | Step::Move(p) => Some(p.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn execute(self) -> Filed {
+        let mut current = self.path.clone();
+        match execute(&self.steps, &mut current) {
+            Ok(kept) => {
+                log::info!("rule applied"; rule = self.rule, path = self.path.display(), now = current.display());
+                let run = self.steps.into_iter().find_map(|s| match s {
+                    Step::Run(mut argv) => {
+                        argv.push(current.to_string_lossy().into_owned());
+                        Some(argv)
+                    }
+                    _ => None,
+                });
+                Filed {
+                    from: self.path,
+                    path: kept.then_some(current),
+                    notify: self.notify && kept,
+                    tags: self.tags,
+                    run,
+                    aged: self.aged,
+                }
+            }
+            // announce it wherever it ended up, so it isn't lost track of
+            Err(e) => {
+                log::warn!("rule failed: {e:#}"; rule = self.rule, path = self.path.display());
+                Filed {
+                    from: self.path,
+                    path: Some(current),
+                    notify: true,
+                    tags: Vec::new(),
+                    run: None,
+                    aged: self.aged,
+                }
+            }
+        }
+    }
+}
+
+/// What became of a file after its rule was carried out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filed {
+    /// Where it was when the rule matched
+    pub from: PathBuf,
+    /// Where it is now; None if it was trashed
+    pub path: Option<PathBuf>,
+    pub notify: bool,
+    pub tags: Vec<String>,
+    /// The rule's command with the file's final path (in the trash, if it
+    /// went there), for the watcher to launch once the rule is done
+    pub run: Option<Vec<String>>,
+    /// It was already in history, so its entry is updated rather than added
+    pub aged: bool,
+}
+
+/// Plan the first matching rule for a newly arrived file. None if no rule
+/// applies and it should be announced as is.
+pub fn prepare(cfg: &Config, path: &Path, now: f64) -> Option<Job> {
+    first_match(cfg, path, now, false)
+}
+
+/// Plan the first rule with a `min_age_days` that now matches a file already
+/// in history. Rules without one had their chance when it arrived.
+pub fn prepare_aged(cfg: &Config, path: &Path, now: f64) -> Option<Job> {
+    first_match(cfg, path, now, true)
+}
+
+fn first_match(cfg: &Config, path: &Path, now: f64, aged: bool) -> Option<Job> {
+    if cfg.rules.is_empty() {
+        return None;
+    }
+    let facts = Facts::new(path, now)?;
+    let (i, rule) = cfg
+        .rules
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| !aged || r.min_age_days.is_some())
+        .find(|(_, r)| mismatch(r, &facts).is_none())?;
+    let name = label(rule, i);
+    match plan(rule, &facts) {
+        Ok(steps) => Some(Job {
+            rule: name,
+            path: path.to_path_buf(),
+            steps,
+            notify: rule.notify,
+            tags: rule.tags.clone(),
+            aged,
+        }),
+        Err(e) => {
+            log::warn!("rule can't apply: {e:#}"; rule = name, path = path.display());
+            None
+        }
+    }
+}
+
+/// `glance rules test PATH`: go through the rules as the watcher would.
+fn test(cfg: &Config, path: &Path) -> Result<()> {
+    let path = path.canonicalize().with_context(|| format!("can't find {}", path.display()))?;
+    let facts = Facts::new(&path, SystemClock.now()).context("not a regular file")?;
+    if cfg.rules.is_empty() {
+        println!("no [[rules]] in the config");
+        return Ok(());
+    }
+    for (i, rule) in cfg.rules.iter().enumerate() {
+        let name = label(rule, i);
+        if let Some(why) = mismatch(rule, &facts) {
+            println!("{name}: no, {why}");
+            continue;
+        }
+        println!("{name}: matches");
+        for step in plan(rule, &facts)? {
+            println!("  {}", step.describe());
+        }
+        if !rule.tags.is_empty() {
+            println!("  tag {}", rule.tags.join(", "));
+        }
+        if !rule.notify || rule.trash {
+            println!("  don't show it in the widget");
+        }
+        return Ok(());
+    }
+    println!("no rule matches; it would be announced as usual");
+    Ok(())
+}
+
+pub fn run(cfg: &Config, command: &RulesCommand) -> Result<()> {
+    match command {
+        RulesCommand::Test { path } => test(cfg, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> Rule {
+        toml::from_str("").unwrap()
+    }
+
+    fn facts(path: &Path, size: u64, age_days: f64) -> Facts<'_> {
+        Facts {
+            path,
+            name: file_name(path),
+            size,
+            mtime: 1_714_564_800.0,
+            age_days,
+            mime: OnceCell::from("application/pdf".to_string()),
+        }
+    }
+
+    #[test]
+    fn every_condition_must_hold() {
+        let path = Path::new("/dl/invoice-42.pdf");
+        let f = facts(path, 2048, 3.0);
+        assert_eq!(mismatch(&rule(), &f), None);
+
+        let r = Rule { glob: Some("invoice*".into()), mime: Some("application/*".into()), ..rule() };
+        assert_eq!(mismatch(&r, &f), None);
+        let r = Rule { dir: Some("/dl".into()), max_size: Some(4096), max_age_days: Some(7.0), ..r };
+        let r = Rule { min_age_days: Some(1.0), ..r };
+        assert_eq!(mismatch(&r, &f), None);
+
+        let cases = [
+            (Rule { glob: Some("*.png".into()), ..rule() }, "name doesn't match \"*.png\""),
+            (Rule { dir: Some("/elsewhere".into()), ..rule() }, "not in /elsewhere"),
+            (Rule { min_size: Some(4096), ..rule() }, "smaller than 4096 bytes"),
+            (Rule { max_size: Some(1024), ..rule() }, "larger than 1024 bytes"),
+            (Rule { min_age_days: Some(30.0), ..rule() }, "less than 30 days old"),
+            (Rule { max_age_days: Some(1.0), ..rule() }, "more than 1 days old"),
+            (Rule { mime: Some("image/*".into()), ..rule() }, "type is application/pdf, not image/*"),
+        ];
+        for (r, why) in cases {
+            assert_eq!(mismatch(&r, &f).as_deref(), Some(why));
+        }
+    }
+
+    #[test]
+    fn templates_fill_in_the_name() {
+        assert_eq!(fill_template("{date} {name}", "a.pdf", "2024-05-01"), "2024-05-01 a.pdf");
+        assert_eq!(fill_template("{stem}-scan.{ext}", "doc.tar.gz", ""), "doc.tar-scan.gz");
+        assert_eq!(fill_template("{stem}.{ext}", "README", ""), "README.");
+    }
+
+    #[test]
+    fn plans_follow_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("invoice.pdf");
+        fs::write(&path, "pdf").unwrap();
+        let f = facts(&path, 3, 0.0);
+        let archive = dir.path().join("archive");
+        let r = Rule {
+            rename: Some("{stem}-filed.{ext}".into()),
+            copy: Some(archive.to_string_lossy().into_owned()),
+            move_to: Some(dir.path().join("invoices").to_string_lossy().into_owned()),
+            run: Some("notify-send Filed".into()),
+            ..rule()
+        };
+        let name = "invoice-filed.pdf";
+        let filed = dir.path().join("invoices").join(name);
+        let steps = plan(&r, &f).unwrap();
+        assert_eq!(
+            steps,
+            [
+                Step::Rename(dir.path().join(name)),
+                Step::Copy(archive.join(name)),
+                Step::Move(filed.clone()),
+                Step::Run(vec!["notify-send".into(), "Filed".into()]),
+            ]
+        );
+
+        // trashing wins over moving, and the command waits for it
+        let r = Rule { trash: true, ..r };
+        let steps = plan(&r, &f).unwrap();
+        assert_eq!(steps[steps.len() - 2..], [Step::Trash, Step::Run(vec!["notify-send".into(), "Filed".into()])]);
+        assert!(!steps.iter().any(|s| matches!(s, Step::Move(_))));
+        let r = Rule { rename: Some("../{name}".into()), ..rule() };
+        assert!(plan(&r, &f).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::log;
+use gtk4::glib;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::process::{Command, Output, Stdio};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Launches external programs (`pkill`, `wl-copy`, `hyprctl`, ...).
+///
+/// Everything glance shells out to goes through [`spawn`] and [`output`] so
+/// tests can swap in a runner that records invocations instead.
+pub trait CommandRunner {
+    /// Start the command without waiting for it to finish.
+    fn spawn(&self, cmd: &mut Command) -> io::Result<()>;
+    /// Run the command to completion and capture its output.
+    fn output(&self, cmd: &mut Command) -> io::Result<Output>;
+    /// Like [`CommandRunner::output`], feeding `input` on stdin.
+    fn output_with_stdin(&self, cmd: &mut Command, input: &[u8]) -> io::Result<Output>;
+    /// Run the command to completion, killing it if it's still going after
+    /// `limit`. Returns whether it had to be killed.
+    fn run_for(&self, cmd: &mut Command, limit: Duration) -> io::Result<bool>;
+    /// Whether `bin` can be found on `$PATH`.
+    fn installed(&self, bin: &str) -> bool;
+}
+
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn spawn(&self, cmd: &mut Command) -> io::Result<()> {
+        let mut child = cmd.spawn()?;
+        // reaped off to the side, so a long-running watcher launching rule
+        // commands doesn't pile up zombies
+        let reaper = std::thread::Builder::new().name("glance-reap".into()).spawn(move || {
+            let _ = child.wait();
+        });
+        if let Err(e) = reaper {
+            log::debug!("can't wait on child: {e}");
+        }
+        Ok(())
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        cmd.output()
+    }
+
+    fn output_with_stdin(&self, cmd: &mut Command, input: &[u8]) -> io::Result<Output> {
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // dropped at the end of the block, closing stdin so the child sees EOF
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        child.wait_with_output()
+    }
+
+    fn run_for(&self, cmd: &mut Command, limit: Duration) -> io::Result<bool> {
+        let mut child = cmd.spawn()?;
+        let start = Instant::now();
+        while child.try_wait()?.is_none() {
+            if start.elapsed() >= limit {
+                child.kill()?;
+                child.wait()?;
+                return Ok(true);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Ok(false)
+    }
+
+    fn installed(&self, bin: &str) -> bool {
+        glib::find_program_in_path(bin).is_some()
+    }
+}
+
+thread_local! {
+    static RUNNER: RefCell<Rc<dyn CommandRunner>> = RefCell::new(Rc::new(SystemRunner));
+}
+
+/// Replace the runner for the current thread. Per-thread so that parallel
+/// tests don't see each other's recorders.
+pub fn set_runner(runner: Rc<dyn CommandRunner>) {
+    RUNNER.with(|r| *r.borrow_mut() = runner);
+}
+
+fn current() -> Rc<dyn CommandRunner> {
+    RUNNER.with(|r| r.borrow().clone())
+}
+
+pub fn spawn(cmd: &mut Command) -> io::Result<()> {
+    current().spawn(cmd)
+}
+
+pub fn output(cmd: &mut Command) -> io::Result<Output> {
+    current().output(cmd)
+}
+
+pub fn output_with_stdin(cmd: &mut Command, input: &[u8]) -> io::Result<Output> {
+    current().output_with_stdin(cmd, input)
+}
+
+pub fn run_for(cmd: &mut Command, limit: Duration) -> io::Result<bool> {
+    current().run_for(cmd, limit)
+}
+
+pub fn installed(bin: &str) -> bool {
+    current().installed(bin)
+}
+
+/// Fire-and-forget [`spawn`]: a failure to launch is logged, not returned.
+pub fn launch(cmd: &mut Command) {
+    if let Err(e) = spawn(cmd) {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        log::warn!("failed to launch {program}: {e}"; program = program);
+    }
+}
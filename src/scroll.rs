@@ -1,23 +1,19 @@
+use crate::clock::SystemClock;
 use crate::config::Config;
 use crate::state::with_history;
+use crate::util::signal_waybar;
 use anyhow::Result;
-use std::process::Command;
 
 pub fn run(cfg: &Config, direction: &str) -> Result<()> {
-    let state_file = Config::state_file();
+    let state_file = cfg.state_file();
     let dir = direction.to_string();
     with_history(&state_file, |history| {
         match dir.as_str() {
-            "up" => history.select_next(),
-            "down" => history.select_prev(),
+            "up" => history.select_next(&SystemClock),
+            "down" => history.select_prev(&SystemClock),
             _ => {}
         }
     })?;
-    let _ = Command::new("pkill")
-        .arg(format!("-RTMIN+{}", cfg.signal_number))
-        .arg("-x")
-        .arg("-o")
-        .arg("waybar")
-        .output();
+    signal_waybar(cfg.signal_number);
     Ok(())
 }
@@ -0,0 +1,169 @@
+use crate::config::Config;
+use crate::state::{FileState, HistoryState};
+use crate::util::glob_match;
+use anyhow::Result;
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+/// Which history entry a command acts on. At most one may be given; with
+/// none, commands act on whatever the widget is showing. Never changes the
+/// persisted selection.
+#[derive(Debug, Clone, Default, Args)]
+#[group(id = "entry", multiple = false)]
+pub struct Selector {
+    /// History index, 0 = newest
+    #[arg(long)]
+    pub index: Option<usize>,
+    /// Newest entry whose file name matches this glob (`*`, `?`, `[a-z]`)
+    #[arg(long, value_name = "GLOB")]
+    pub name: Option<String>,
+    /// The newest entry, even if the widget has hidden it
+    #[arg(long)]
+    pub latest: bool,
+    /// The entry for this file
+    #[arg(long, value_name = "PATH")]
+    pub path: Option<PathBuf>,
+}
+
+impl Selector {
+    pub fn index(i: usize) -> Self {
+        Self { index: Some(i), ..Default::default() }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.index.is_some() || self.name.is_some() || self.latest || self.path.is_some()
+    }
+
+    /// Index of the chosen entry; `Ok(None)` when no selector was given,
+    /// an error when one was but nothing matches.
+    pub fn pick(&self, history: &HistoryState) -> Result<Option<usize>> {
+        let entries = &history.entries;
+        let found = if let Some(i) = self.index {
+            (i < entries.len()).then_some(i)
+        } else if let Some(glob) = &self.name {
+            entries.iter().position(|e| matches_name(e, glob))
+        } else if self.latest {
+            (!entries.is_empty()).then_some(0)
+        } else if let Some(path) = &self.path {
+            let path = absolute(path);
+            entries.iter().position(|e| e.path == path || e.files.contains(&path))
+        } else {
+            return Ok(None);
+        };
+        match found {
+            Some(i) => Ok(Some(i)),
+            None => anyhow::bail!("no history entry matches {}", self.describe()),
+        }
+    }
+
+    /// Index of the entry an action should act on: the selector's match, or
+    /// without one the entry the widget is currently showing.
+    pub fn target_index(
+        &self,
+        history: &HistoryState,
+        now: f64,
+        cfg: &Config,
+    ) -> Result<Option<usize>> {
+        Ok(match self.pick(history)? {
+            Some(i) => Some(i),
+            None => history.visible_entry(now, cfg).map(|_| history.selected),
+        })
+    }
+
+    /// The entry [`Selector::target_index`] points at.
+    pub fn target<'a>(
+        &self,
+        history: &'a HistoryState,
+        now: f64,
+        cfg: &Config,
+    ) -> Result<Option<&'a FileState>> {
+        Ok(self
+            .target_index(history, now, cfg)?
+            .and_then(|i| history.entries.get(i)))
+    }
+
+    fn describe(&self) -> String {
+        if let Some(i) = self.index {
+            format!("--index {i}")
+        } else if let Some(glob) = &self.name {
+            format!("--name {glob}")
+        } else if let Some(path) = &self.path {
+            format!("--path {}", path.display())
+        } else {
+            "--latest".into()
+        }
+    }
+}
+
+/// Batches match on their own name or any member's file name.
+fn matches_name(e: &FileState, glob: &str) -> bool {
+    glob_match(glob, &e.name)
+        || e.files.iter().any(|f| {
+            f.file_name()
+                .is_some_and(|n| glob_match(glob, &n.to_string_lossy()))
+        })
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    let path = PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref());
+    if path.is_absolute() {
+        path
+    } else {
+        std::env::current_dir().map(|d| d.join(&path)).unwrap_or(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> FileState {
+        let path = PathBuf::from(path);
+        FileState {
+            name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path,
+            ..Default::default()
+        }
+    }
+
+    fn history() -> HistoryState {
+        let mut batch = entry("/dl");
+        batch.name = "2 files in dl".into();
+        batch.files = vec!["/dl/a.zip".into(), "/dl/b.zip".into()];
+        HistoryState {
+            entries: vec![entry("/s/new.png"), batch, entry("/s/old.png"), entry("/d/report.pdf")],
+            selected: 3,
+            ..Default::default()
+        }
+    }
+
+    fn pick(sel: Selector) -> Result<Option<usize>> {
+        sel.pick(&history())
+    }
+
+    #[test]
+    fn unset_selector_picks_nothing() {
+        assert!(!Selector::default().is_set());
+        assert_eq!(pick(Selector::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn picks_by_index_latest_name_and_path() {
+        assert_eq!(pick(Selector::index(2)).unwrap(), Some(2));
+        assert_eq!(pick(Selector { latest: true, ..Default::default() }).unwrap(), Some(0));
+        let name = |g: &str| Selector { name: Some(g.into()), ..Default::default() };
+        assert_eq!(pick(name("*.png")).unwrap(), Some(0));
+        assert_eq!(pick(name("report.*")).unwrap(), Some(3));
+        assert_eq!(pick(name("b.zip")).unwrap(), Some(1));
+        let path = |p: &str| Selector { path: Some(p.into()), ..Default::default() };
+        assert_eq!(pick(path("/s/old.png")).unwrap(), Some(2));
+        assert_eq!(pick(path("/dl/a.zip")).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn explicit_selector_without_match_is_an_error() {
+        let err = pick(Selector::index(9)).unwrap_err();
+        assert_eq!(err.to_string(), "no history entry matches --index 9");
+        assert!(pick(Selector { name: Some("*.gif".into()), ..Default::default() }).is_err());
+    }
+}
@@ -1,16 +1,23 @@
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::log;
+use crate::util::remove_stale;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::fs::File;
+use std::io::Write;
 use std::os::fd::AsRawFd;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
 
-struct FileLock {
+/// An exclusive `flock` on a sibling `.lock` file, for read-modify-write of
+/// small runtime files.
+pub(crate) struct FileLock {
     _file: File,
 }
 
 impl FileLock {
-    fn acquire(state_file: &Path) -> Result<Self> {
+    pub(crate) fn acquire(state_file: &Path) -> Result<Self> {
         Self::acquire_timeout(state_file, std::time::Duration::from_secs(2))
     }
 
@@ -45,43 +52,146 @@ impl FileLock {
 
 // Lock is released when _file is dropped
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileState {
     pub path: PathBuf,
     pub name: String,
     pub size: u64,
     pub time: f64,
+    /// Deleted or moved out of the watched dirs since it was announced.
+    #[serde(default)]
+    pub missing: bool,
+    /// Kept when the history is full and older entries are dropped.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Set for a batch entry: the files that arrived together. `path` is then
+    /// the directory they landed in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+    /// Labels given by a matching `[[rules]]` entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Already looked over by the watcher's sweep for age rules
+    /// (`min_age_days`), which acts on an entry only once.
+    #[serde(default)]
+    pub swept: bool,
 }
 
 impl FileState {
-    pub fn new(path: PathBuf) -> Result<Self> {
+    pub fn new(path: PathBuf, clock: &dyn Clock) -> Result<Self> {
         let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
         let name = path
             .file_name()
             .unwrap_or_default()
             .to_string_lossy()
             .into_owned();
-        let time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)?
-            .as_secs_f64();
-        Ok(Self { path, name, size, time })
+        let time = clock.now();
+        Ok(Self { path, name, size, time, ..Default::default() })
+    }
+
+    pub fn is_batch(&self) -> bool {
+        !self.files.is_empty()
     }
 
-    pub fn is_expired(&self, dismiss_secs: u64) -> bool {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64();
+    /// Whether `other` is this same arrival: same path, and the same time
+    /// (a path can come and go in history more than once).
+    pub fn same_entry(&self, other: &FileState) -> bool {
+        self.path == other.path && self.time == other.time
+    }
+
+    /// Every file this entry stands for.
+    pub fn paths(&self) -> Vec<&Path> {
+        if self.is_batch() {
+            self.files.iter().map(PathBuf::as_path).collect()
+        } else {
+            vec![self.path.as_path()]
+        }
+    }
+
+    /// The directory the file (or batch) arrived in.
+    pub fn dir(&self) -> Option<&Path> {
+        if self.is_batch() {
+            Some(&self.path)
+        } else {
+            self.path.parent()
+        }
+    }
+
+    /// Fold another arrival from the same directory into this entry,
+    /// turning it into a batch. The batch keeps the time of its first file.
+    fn absorb(&mut self, other: FileState) {
+        if !self.is_batch() {
+            let dir = self.dir().map(Path::to_path_buf).unwrap_or_default();
+            self.files.push(std::mem::replace(&mut self.path, dir));
+        }
+        self.files.push(other.path);
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self.size += other.size;
+        self.rename_batch();
+    }
+
+    pub(crate) fn rename_batch(&mut self) {
+        let dir_name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let n = self.files.len();
+        let noun = if n == 1 { "file" } else { "files" };
+        self.name = format!("{n} {noun} in {dir_name}");
+    }
+
+    pub fn is_expired(&self, dismiss_secs: u64, now: f64) -> bool {
         now - self.time > (dismiss_secs + 2) as f64
     }
 }
 
+/// Why the widget is (or isn't) showing the selected entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// Nothing in history to show.
+    Empty,
+    /// The newest entry, still inside its dismiss window.
+    Fresh,
+    /// The user scrolled within the last `dismiss_seconds`.
+    Scrolled,
+    /// The dismiss timer ran out.
+    Expired,
+    /// Hidden on request via `glance dismiss`.
+    Dismissed,
+}
+
+impl Visibility {
+    pub fn is_visible(self) -> bool {
+        matches!(self, Visibility::Fresh | Visibility::Scrolled)
+    }
+}
+
+/// Version written to the state file. Bump it together with a new entry in
+/// [`MIGRATIONS`] whenever the on-disk shape changes.
+pub const SCHEMA_VERSION: u32 = 5;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HistoryState {
+    pub version: u32,
     pub entries: Vec<FileState>,
     pub selected: usize,
     #[serde(default)]
     pub last_scroll: f64,
+    #[serde(default)]
+    pub dismissed: bool,
+}
+
+impl Default for HistoryState {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            entries: Vec::new(),
+            selected: 0,
+            last_scroll: 0.0,
+            dismissed: false,
+        }
+    }
 }
 
 impl HistoryState {
@@ -89,54 +199,381 @@ impl HistoryState {
         self.entries.get(self.selected)
     }
 
+    /// Single source of truth for whether the selected entry is showing.
+    pub fn visibility(&self, now: f64, cfg: &Config) -> Visibility {
+        let Some(entry) = self.current() else {
+            return Visibility::Empty;
+        };
+        if self.dismissed {
+            return Visibility::Dismissed;
+        }
+        if self.last_scroll > 0.0 && now - self.last_scroll < cfg.dismiss_seconds as f64 {
+            return Visibility::Scrolled;
+        }
+        if self.selected == 0 && !entry.is_expired(cfg.dismiss_seconds, now) {
+            return Visibility::Fresh;
+        }
+        Visibility::Expired
+    }
+
+    /// The selected entry, if the widget is currently showing it.
+    pub fn visible_entry(&self, now: f64, cfg: &Config) -> Option<&FileState> {
+        if self.visibility(now, cfg).is_visible() {
+            self.current()
+        } else {
+            None
+        }
+    }
+
+    /// Seconds left before the selected entry auto-dismisses.
+    pub fn remaining_secs(&self, now: f64, cfg: &Config) -> Option<f64> {
+        let dismiss = cfg.dismiss_seconds as f64;
+        let started = match self.visibility(now, cfg) {
+            Visibility::Fresh => self.current()?.time,
+            Visibility::Scrolled => self.last_scroll,
+            _ => return None,
+        };
+        Some((dismiss - (now - started)).max(0.0))
+    }
+
+    /// Push a new arrival, or fold it into the newest entry if that one
+    /// started in the same directory less than `window` seconds earlier, so
+    /// a steady trickle can't keep a batch open. Returns true if it was
+    /// folded in.
+    pub fn push_grouped(&mut self, entry: FileState, window: f64, max_size: usize) -> bool {
+        if let Some(top) = self.entries.first_mut() {
+            let same_dir = top.dir().is_some() && top.dir() == entry.dir();
+            if window > 0.0 && !top.missing && same_dir && entry.time - top.time <= window {
+                top.absorb(entry);
+                self.selected = 0;
+                self.last_scroll = 0.0;
+                self.dismissed = false;
+                return true;
+            }
+        }
+        self.push(entry, max_size);
+        false
+    }
+
     pub fn push(&mut self, entry: FileState, max_size: usize) {
         self.entries.insert(0, entry);
-        self.entries.truncate(max_size);
+        self.truncate(max_size);
         self.selected = 0;
         self.last_scroll = 0.0;
+        self.dismissed = false;
+    }
+
+    /// Drop the oldest unpinned entries until at most `max_size` remain.
+    /// The newest entry and pinned ones stay, even if that leaves the
+    /// history over size.
+    fn truncate(&mut self, max_size: usize) {
+        while self.entries.len() > max_size.max(1) {
+            match self.entries.iter().skip(1).rposition(|e| !e.pinned) {
+                Some(i) => {
+                    self.entries.remove(i + 1);
+                }
+                None => break,
+            }
+        }
     }
 
-    pub fn select_prev(&mut self) {
+    /// Follow a rename of a file already in history. Returns false if no
+    /// entry had the old path.
+    pub fn rename_path(&mut self, from: &Path, to: &Path) -> bool {
+        let mut found = false;
+        for e in self.entries.iter_mut() {
+            for f in e.files.iter_mut().filter(|f| *f == from) {
+                *f = to.to_path_buf();
+                found = true;
+            }
+        }
+        for e in self.entries.iter_mut().filter(|e| !e.is_batch() && e.path == from) {
+            e.path = to.to_path_buf();
+            e.name = to
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            e.missing = false;
+            found = true;
+        }
+        found
+    }
+
+    /// Add `tags` to the entry for `path` (a file, or one in a batch).
+    pub fn tag(&mut self, path: &Path, tags: &[String]) -> bool {
+        let mut changed = false;
+        for e in self.entries.iter_mut().filter(|e| e.paths().contains(&path)) {
+            for tag in tags {
+                if !e.tags.contains(tag) {
+                    e.tags.push(tag.clone());
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Flag entries for a file that has been deleted or moved away.
+    pub fn mark_missing(&mut self, path: &Path) -> bool {
+        let mut found = false;
+        // a batch loses the file, and only goes missing once it's empty
+        for e in self.entries.iter_mut().filter(|e| e.files.iter().any(|f| f == path)) {
+            e.files.retain(|f| f != path);
+            e.rename_batch();
+            e.missing = e.files.is_empty();
+            found = true;
+        }
+        for e in self.entries.iter_mut().filter(|e| !e.is_batch() && e.path == path && !e.missing) {
+            e.missing = true;
+            found = true;
+        }
+        found
+    }
+
+    /// Take out `entry`, keeping the selection on the same entry where
+    /// possible. Returns where it was, for [`HistoryState::restore`].
+    pub fn remove(&mut self, entry: &FileState) -> Option<(usize, FileState)> {
+        let index = self.entries.iter().position(|e| e.same_entry(entry))?;
+        let entry = self.entries.remove(index);
+        if index < self.selected {
+            self.selected -= 1;
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        Some((index, entry))
+    }
+
+    /// Put a removed entry back where it was. An entry that was kept, having
+    /// lost only some of its files, gets those back instead. Returns whether
+    /// anything changed.
+    pub fn restore(&mut self, index: usize, entry: FileState) -> bool {
+        if let Some(kept) = self.entries.iter_mut().find(|e| e.same_entry(&entry)) {
+            let lost: Vec<PathBuf> =
+                entry.files.into_iter().filter(|f| !kept.files.contains(f)).collect();
+            let changed = !lost.is_empty() || kept.missing;
+            if !lost.is_empty() {
+                kept.files.extend(lost);
+                kept.rename_batch();
+            }
+            kept.missing = false;
+            return changed;
+        }
+        let index = index.min(self.entries.len());
+        if index <= self.selected && !self.entries.is_empty() {
+            self.selected += 1;
+        }
+        self.entries.insert(index, entry);
+        true
+    }
+
+    /// Flip the pin on `entry`; returns the new state, or None if it's no
+    /// longer in history.
+    pub fn toggle_pin(&mut self, entry: &FileState) -> Option<bool> {
+        let e = self.entries.iter_mut().find(|e| e.same_entry(entry))?;
+        e.pinned = !e.pinned;
+        Some(e.pinned)
+    }
+
+    /// Files behind the given entries, in the order given. Out-of-range
+    /// indices and files already listed are skipped.
+    pub fn paths_at(&self, indices: &[usize]) -> Vec<&Path> {
+        let mut out: Vec<&Path> = Vec::new();
+        for e in indices.iter().filter_map(|&i| self.entries.get(i)) {
+            for p in e.paths() {
+                if !out.contains(&p) {
+                    out.push(p);
+                }
+            }
+        }
+        out
+    }
+
+    pub fn dismiss(&mut self) {
+        self.dismissed = true;
+        self.last_scroll = 0.0;
+    }
+
+    /// Jump straight to entry `index` (clamped), as if scrolled there.
+    pub fn select(&mut self, index: usize, clock: &dyn Clock) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+        self.dismissed = false;
+        self.last_scroll = clock.now();
+    }
+
+    pub fn select_prev(&mut self, clock: &dyn Clock) {
         if self.selected + 1 < self.entries.len() {
             self.selected += 1;
         }
-        self.last_scroll = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64();
+        self.dismissed = false;
+        self.last_scroll = clock.now();
     }
 
-    pub fn select_next(&mut self) {
+    pub fn select_next(&mut self, clock: &dyn Clock) {
         if self.selected > 0 {
             self.selected -= 1;
         }
-        self.last_scroll = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64();
+        self.dismissed = false;
+        self.last_scroll = clock.now();
+    }
+}
+
+type Migration = fn(Value) -> Option<Value>;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`.
+const MIGRATIONS: &[Migration] =
+    &[migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4, migrate_v4_to_v5];
+
+/// v1 (0.1–0.2): a single bare `FileState`, no history.
+fn migrate_v1_to_v2(v: Value) -> Option<Value> {
+    Some(json!({ "entries": [v], "selected": 0 }))
+}
+
+/// v2 (0.3–0.4): unversioned `{entries, selected, last_scroll}`.
+fn migrate_v2_to_v3(mut v: Value) -> Option<Value> {
+    v.as_object_mut()?.insert("version".into(), json!(3));
+    Some(v)
+}
+
+/// v3: versioned, with `dismissed`. v4 entries gain `missing` and `pinned`,
+/// plus `files` for batches and `tags` from rules; all default to off/empty.
+fn migrate_v3_to_v4(mut v: Value) -> Option<Value> {
+    let obj = v.as_object_mut()?;
+    for entry in obj.get_mut("entries")?.as_array_mut()? {
+        let entry = entry.as_object_mut()?;
+        entry.entry("missing").or_insert(json!(false));
+        entry.entry("pinned").or_insert(json!(false));
     }
+    obj.insert("version".into(), json!(4));
+    Some(v)
+}
 
+/// v5 entries gain `swept`, set once an age rule has had its chance at them.
+fn migrate_v4_to_v5(mut v: Value) -> Option<Value> {
+    let obj = v.as_object_mut()?;
+    for entry in obj.get_mut("entries")?.as_array_mut()? {
+        entry.as_object_mut()?.entry("swept").or_insert(json!(false));
+    }
+    obj.insert("version".into(), json!(5));
+    Some(v)
 }
 
-fn parse_history(content: &str) -> HistoryState {
-    // try new format
-    if let Ok(h) = serde_json::from_str::<HistoryState>(content) {
-        return h;
+/// Work out which schema a document was written with. Files before v3 have
+/// no `version` field, so their shape gives them away.
+fn detect_version(v: &Value) -> Option<u32> {
+    let obj = v.as_object()?;
+    if let Some(version) = obj.get("version") {
+        return version.as_u64().map(|n| n as u32);
     }
-    // backward compat: old single-FileState format
-    if let Ok(fs) = serde_json::from_str::<FileState>(content) {
-        return HistoryState { entries: vec![fs], selected: 0, last_scroll: 0.0 };
+    if obj.contains_key("entries") {
+        Some(2)
+    } else if obj.contains_key("path") {
+        Some(1)
+    } else {
+        None
     }
-    HistoryState { entries: vec![], selected: 0, last_scroll: 0.0 }
 }
 
-pub fn read_history(state_file: &Path) -> HistoryState {
-    let _lock = FileLock::acquire(state_file).ok();
+fn parse_history(content: &str) -> Option<HistoryState> {
+    let mut value: Value = serde_json::from_str(content).ok()?;
+    let mut version = detect_version(&value)?;
+    if version == 0 {
+        return None;
+    }
+    // read what we understand of a newer file, but keep its version so
+    // nothing writes it back without the fields we don't know about
+    if version > SCHEMA_VERSION {
+        log::warn!(
+            "state file is schema v{version}, newer than this glance (v{SCHEMA_VERSION}); \
+             reading it as best we can and leaving it alone"
+        );
+    }
+    while version < SCHEMA_VERSION {
+        value = MIGRATIONS[version as usize - 1](value)?;
+        version += 1;
+    }
+    serde_json::from_value(value).ok()
+}
+
+fn sibling(state_file: &Path, suffix: &str) -> PathBuf {
+    let mut name = state_file.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn backup_file(state_file: &Path) -> PathBuf {
+    sibling(state_file, ".bak")
+}
+
+/// Read history, falling back to the `.bak` copy if the state file is
+/// corrupt. The flag is false when what's on disk couldn't be trusted.
+fn load(state_file: &Path) -> (HistoryState, bool) {
     let content = match std::fs::read_to_string(state_file) {
         Ok(c) => c,
-        Err(_) => return HistoryState { entries: vec![], selected: 0, last_scroll: 0.0 },
+        Err(_) => return (HistoryState::default(), true),
     };
-    parse_history(&content)
+    if content.trim().is_empty() {
+        return (HistoryState::default(), true);
+    }
+    if let Some(h) = parse_history(&content) {
+        return (h, true);
+    }
+
+    let backup = backup_file(state_file);
+    match std::fs::read_to_string(&backup).ok().and_then(|c| parse_history(&c)) {
+        Some(h) => {
+            log::warn!(
+                "{} is corrupt, recovered history from {}",
+                state_file.display(),
+                backup.display()
+            );
+            (h, false)
+        }
+        None => {
+            log::warn!(
+                "{} is corrupt and no usable backup exists, starting empty",
+                state_file.display()
+            );
+            (HistoryState::default(), false)
+        }
+    }
+}
+
+/// Replace the state file without ever exposing a half-written one: write a
+/// temp file, fsync it, and rename it into place. If the old file was good,
+/// it's kept as `.bak` first.
+fn write_atomic(state_file: &Path, json: &str, keep_backup: bool) -> Result<()> {
+    let tmp = sibling(state_file, ".tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+    }
+
+    if keep_backup && state_file.exists() {
+        // hard-link so the backup is the exact previous file, not a rewrite
+        let backup = backup_file(state_file);
+        let backup_tmp = sibling(state_file, ".bak.tmp");
+        remove_stale(&backup_tmp);
+        let linked = std::fs::hard_link(state_file, &backup_tmp)
+            .or_else(|_| std::fs::copy(state_file, &backup_tmp).map(|_| ()));
+        // a missing backup only matters on the next corruption; keep going
+        if let Err(e) = linked.and_then(|_| std::fs::rename(&backup_tmp, &backup)) {
+            log::warn!("failed to back up {}: {e}", state_file.display());
+        }
+    }
+
+    std::fs::rename(&tmp, state_file)?;
+    if let Some(dir) = state_file.parent() {
+        if let Err(e) = File::open(dir).and_then(|d| d.sync_all()) {
+            log::debug!("fsync of {} failed: {e}", dir.display());
+        }
+    }
+    Ok(())
+}
+
+pub fn read_history(state_file: &Path) -> HistoryState {
+    let _lock = FileLock::acquire(state_file).ok();
+    load(state_file).0
 }
 
 /// Atomically read, modify, and write history under a single lock.
@@ -146,10 +583,546 @@ where
     F: FnOnce(&mut HistoryState),
 {
     let _lock = FileLock::acquire(state_file)?;
-    let content = std::fs::read_to_string(state_file).unwrap_or_default();
-    let mut history = parse_history(&content);
+    let (mut history, was_good) = load(state_file);
     f(&mut history);
-    let json = serde_json::to_string(&history)?;
-    std::fs::write(state_file, json)?;
-    Ok(())
+    save(state_file, &history, was_good)
+}
+
+/// Like [`with_history`], but only writes if `f` says it changed something,
+/// for updates that usually don't apply (events about unrelated files).
+pub fn update_history<F>(state_file: &Path, f: F) -> Result<bool>
+where
+    F: FnOnce(&mut HistoryState) -> bool,
+{
+    let _lock = FileLock::acquire(state_file)?;
+    let (mut history, was_good) = load(state_file);
+    if !f(&mut history) {
+        return Ok(false);
+    }
+    save(state_file, &history, was_good)?;
+    Ok(true)
+}
+
+/// Write back history read by [`load`], unless it came from a newer glance
+/// whose fields would be lost.
+fn save(state_file: &Path, history: &HistoryState, was_good: bool) -> Result<()> {
+    if history.version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "{} is schema v{}, newer than this glance (v{SCHEMA_VERSION}); not overwriting it",
+            state_file.display(),
+            history.version
+        );
+    }
+    let json = serde_json::to_string(history)?;
+    // don't let a corrupt file overwrite the good backup we recovered from
+    write_atomic(state_file, &json, was_good)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::time::{Duration, Instant};
+
+    const T0: f64 = 1_000_000.0;
+
+    fn entry(name: &str, time: f64) -> FileState {
+        FileState {
+            path: PathBuf::from("/tmp").join(name),
+            name: name.into(),
+            size: 0,
+            time,
+            ..Default::default()
+        }
+    }
+
+    fn history(entries: Vec<FileState>) -> HistoryState {
+        HistoryState { entries, ..Default::default() }
+    }
+
+    fn cfg() -> Config {
+        Config { dismiss_seconds: 10, ..Default::default() }
+    }
+
+    #[test]
+    fn empty_history_is_empty() {
+        let h = history(vec![]);
+        assert_eq!(h.visibility(T0, &cfg()), Visibility::Empty);
+        assert!(h.visible_entry(T0, &cfg()).is_none());
+    }
+
+    #[test]
+    fn newest_entry_is_fresh_until_grace_period_ends() {
+        let h = history(vec![entry("a.png", T0)]);
+        assert_eq!(h.visibility(T0 + 5.0, &cfg()), Visibility::Fresh);
+        assert_eq!(h.visibility(T0 + 12.0, &cfg()), Visibility::Fresh);
+        assert_eq!(h.visibility(T0 + 12.5, &cfg()), Visibility::Expired);
+        assert!(h.visible_entry(T0 + 13.0, &cfg()).is_none());
+    }
+
+    #[test]
+    fn scrolling_keeps_older_entry_visible_then_expires() {
+        let mut h = history(vec![entry("b.png", T0), entry("a.png", T0 - 100.0)]);
+        h.selected = 1;
+        h.last_scroll = T0 + 50.0;
+        assert_eq!(h.visibility(T0 + 55.0, &cfg()), Visibility::Scrolled);
+        assert_eq!(h.visible_entry(T0 + 55.0, &cfg()).unwrap().name, "a.png");
+        assert_eq!(h.visibility(T0 + 60.0, &cfg()), Visibility::Expired);
+    }
+
+    #[test]
+    fn scrolled_away_without_recent_scroll_is_expired() {
+        let mut h = history(vec![entry("b.png", T0), entry("a.png", T0)]);
+        h.selected = 1;
+        assert_eq!(h.visibility(T0 + 1.0, &cfg()), Visibility::Expired);
+    }
+
+    #[test]
+    fn dismiss_hides_until_next_push_or_scroll() {
+        let mut h = history(vec![entry("a.png", T0)]);
+        h.dismiss();
+        assert_eq!(h.visibility(T0 + 1.0, &cfg()), Visibility::Dismissed);
+        assert!(h.remaining_secs(T0 + 1.0, &cfg()).is_none());
+
+        h.push(entry("b.png", T0 + 2.0), 5);
+        assert_eq!(h.visibility(T0 + 3.0, &cfg()), Visibility::Fresh);
+
+        h.dismiss();
+        h.select_prev(&ManualClock::new(T0 + 4.0));
+        assert!(!h.dismissed);
+    }
+
+    #[test]
+    fn remaining_counts_from_arrival_or_last_scroll() {
+        let mut h = history(vec![entry("a.png", T0), entry("b.png", T0)]);
+        assert_eq!(h.remaining_secs(T0 + 4.0, &cfg()), Some(6.0));
+        assert_eq!(h.remaining_secs(T0 + 11.0, &cfg()), Some(0.0));
+
+        h.selected = 1;
+        h.last_scroll = T0 + 20.0;
+        assert_eq!(h.remaining_secs(T0 + 23.0, &cfg()), Some(7.0));
+    }
+
+    #[test]
+    fn new_entry_is_stamped_by_the_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shot.png");
+        std::fs::write(&path, b"12345").unwrap();
+
+        let st = FileState::new(path.clone(), &ManualClock::new(T0)).unwrap();
+        assert_eq!(st.name, "shot.png");
+        assert_eq!(st.size, 5);
+        assert_eq!(st.time, T0);
+    }
+
+    #[test]
+    fn expiry_includes_two_second_grace() {
+        let e = entry("a.png", T0);
+        assert!(!e.is_expired(10, T0 + 12.0));
+        assert!(e.is_expired(10, T0 + 12.001));
+        assert!(!e.is_expired(0, T0 + 2.0));
+    }
+
+    #[test]
+    fn push_prepends_truncates_and_resets_selection() {
+        let mut h = history(vec![]);
+        for i in 0..4 {
+            h.push(entry(&format!("{i}.png"), T0 + i as f64), 3);
+        }
+        let names: Vec<_> = h.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["3.png", "2.png", "1.png"]);
+
+        h.selected = 2;
+        h.last_scroll = T0;
+        h.push(entry("4.png", T0 + 5.0), 3);
+        assert_eq!(h.selected, 0);
+        assert_eq!(h.last_scroll, 0.0);
+        assert_eq!(h.entries.len(), 3);
+    }
+
+    #[test]
+    fn truncation_keeps_pinned_entries() {
+        let mut old = entry("old.png", T0);
+        old.pinned = true;
+        let mut h = history(vec![entry("mid.png", T0 + 1.0), old]);
+        h.push(entry("new.png", T0 + 2.0), 2);
+        assert_eq!(names(&h), ["new.png", "old.png"]);
+        assert!(h.entries[1].pinned);
+
+        // everything pinned: nothing left to drop
+        for e in h.entries.iter_mut() {
+            e.pinned = true;
+        }
+        h.push(entry("newest.png", T0 + 3.0), 2);
+        assert_eq!(names(&h), ["newest.png", "new.png", "old.png"]);
+    }
+
+    #[test]
+    fn removed_entries_restore_in_place() {
+        let mut h = history(vec![entry("c.png", T0), entry("b.png", T0), entry("a.png", T0)]);
+        h.selected = 2;
+        // an earlier arrival at the same path is a different entry
+        assert!(h.remove(&entry("b.png", T0 - 1.0)).is_none());
+        let (index, b) = h.remove(&entry("b.png", T0)).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(names(&h), ["c.png", "a.png"]);
+        assert_eq!(h.selected, 1, "still on a.png");
+        assert!(h.remove(&b).is_none());
+
+        assert!(h.restore(index, b.clone()));
+        assert_eq!(names(&h), ["c.png", "b.png", "a.png"]);
+        assert_eq!(h.selected, 2);
+        assert!(!h.restore(index, b), "already back");
+
+        // removing the selected last entry moves the selection up
+        h.remove(&entry("a.png", T0));
+        assert_eq!(h.selected, 1);
+    }
+
+    #[test]
+    fn kept_batches_get_their_files_back() {
+        let mut h = HistoryState::default();
+        h.push_grouped(arrival("/d", "a", T0), 2.0, 5);
+        h.push_grouped(arrival("/d", "b", T0), 2.0, 5);
+        let whole = h.entries[0].clone();
+        assert!(h.mark_missing(Path::new("/d/b")));
+        assert_eq!(h.entries[0].files, [PathBuf::from("/d/a")]);
+
+        assert!(h.restore(0, whole.clone()));
+        assert_eq!(h.entries.len(), 1);
+        assert_eq!(h.entries[0].files, whole.files);
+        assert_eq!(h.entries[0].name, "2 files in d");
+    }
+
+    #[test]
+    fn pins_toggle_on_the_same_entry() {
+        let mut h = history(vec![entry("a.png", T0)]);
+        assert_eq!(h.toggle_pin(&entry("a.png", T0)), Some(true));
+        assert!(h.entries[0].pinned);
+        assert_eq!(h.toggle_pin(&entry("a.png", T0)), Some(false));
+        assert_eq!(h.toggle_pin(&entry("a.png", T0 + 5.0)), None);
+        assert_eq!(h.toggle_pin(&entry("b.png", T0)), None);
+    }
+
+    #[test]
+    fn scrolling_is_clamped_and_records_scroll_time() {
+        let clock = ManualClock::new(T0);
+        let mut h = history(vec![entry("b.png", T0), entry("a.png", T0)]);
+
+        h.select_next(&clock);
+        assert_eq!(h.selected, 0);
+        assert_eq!(h.last_scroll, T0);
+
+        clock.advance(3.0);
+        h.select_prev(&clock);
+        h.select_prev(&clock);
+        assert_eq!(h.selected, 1);
+        assert_eq!(h.last_scroll, T0 + 3.0);
+
+        h.dismiss();
+        h.select(7, &clock);
+        assert_eq!(h.selected, 1);
+        assert!(!h.dismissed);
+        h.select(0, &clock);
+        assert_eq!(h.selected, 0);
+    }
+
+    fn fixture(name: &str) -> HistoryState {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        parse_history(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn migrates_v1_single_entry_fixture() {
+        let h = fixture("state-v1.json");
+        assert_eq!(h.version, SCHEMA_VERSION);
+        assert_eq!(h.entries.len(), 1);
+        assert_eq!(h.entries[0].name, "shot.png");
+        assert_eq!(h.entries[0].size, 48213);
+        assert_eq!(h.selected, 0);
+        assert_eq!(h.last_scroll, 0.0);
+    }
+
+    #[test]
+    fn migrates_v2_fixtures() {
+        let h = fixture("state-v2-0.3.json");
+        assert_eq!(h.version, SCHEMA_VERSION);
+        assert_eq!(h.entries.len(), 2);
+        assert_eq!(h.selected, 1);
+        assert_eq!(h.last_scroll, 0.0);
+
+        let h = fixture("state-v2-0.4.json");
+        assert_eq!(h.entries[0].name, "report.pdf");
+        assert_eq!(h.last_scroll, 1718000105.0);
+        assert!(!h.dismissed);
+    }
+
+    #[test]
+    fn migrates_v3_fixture() {
+        let h = fixture("state-v3.json");
+        assert_eq!(h.version, SCHEMA_VERSION);
+        assert_eq!(h.entries.len(), 2);
+        assert!(h.dismissed);
+        assert!(h.entries.iter().all(|e| !e.missing && !e.pinned));
+        assert!(h.entries.iter().all(|e| e.files.is_empty() && e.tags.is_empty()));
+    }
+
+    #[test]
+    fn migrates_v4_fixture() {
+        let h = fixture("state-v4.json");
+        assert_eq!(h.version, SCHEMA_VERSION);
+        assert_eq!(names(&h), ["3 files in Screenshots", "report.pdf", "old.png"]);
+        assert_eq!(h.entries[0].files.len(), 3);
+        assert_eq!(h.entries[1].tags, ["invoice"]);
+        assert!(h.entries[1].pinned);
+        assert!(h.entries[2].missing);
+        assert!(h.entries.iter().all(|e| !e.swept));
+    }
+
+    #[test]
+    fn reads_current_fixture() {
+        let h = fixture("state-v5.json");
+        assert_eq!(h.version, 5);
+        assert_eq!(names(&h), ["3 files in Screenshots", "report.pdf", "old.png"]);
+        assert!(h.entries[1].pinned);
+        assert!(h.entries[2].swept);
+        assert!(!h.entries[0].swept);
+    }
+
+    #[test]
+    fn every_fixture_round_trips_at_current_version() {
+        let fixtures = [
+            "state-v1.json",
+            "state-v2-0.3.json",
+            "state-v2-0.4.json",
+            "state-v3.json",
+            "state-v4.json",
+            "state-v5.json",
+        ];
+        for name in fixtures {
+            let h = fixture(name);
+            let written = serde_json::to_string(&h).unwrap();
+            let v: Value = serde_json::from_str(&written).unwrap();
+            assert_eq!(v["version"], SCHEMA_VERSION, "{name}");
+            let again = parse_history(&written).unwrap();
+            assert_eq!(names(&again), names(&h), "{name}");
+        }
+    }
+
+    #[test]
+    fn newer_schema_is_read_best_effort() {
+        let json = r#"{"version":99,"entries":[{"path":"/a","name":"a","size":1,"time":1.0,"hash":"x"}],"selected":0,"shiny":true}"#;
+        let h = parse_history(json).unwrap();
+        assert_eq!(names(&h), ["a"]);
+        assert_eq!(h.version, 99);
+    }
+
+    #[test]
+    fn newer_schema_is_never_written_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+        let json = r#"{"version":99,"entries":[],"selected":0,"shiny":true}"#;
+        std::fs::write(&state_file, json).unwrap();
+
+        assert!(with_history(&state_file, |h| h.push(entry("a.png", T0), 5)).is_err());
+        assert!(update_history(&state_file, |_| true).is_err());
+        assert_eq!(std::fs::read_to_string(&state_file).unwrap(), json);
+    }
+
+    #[test]
+    fn garbage_does_not_parse() {
+        assert!(parse_history("").is_none());
+        assert!(parse_history("{\"entries\":[").is_none());
+        assert!(parse_history("{\"version\":0,\"entries\":[]}").is_none());
+        assert!(parse_history("[1, 2]").is_none());
+    }
+
+    fn names(h: &HistoryState) -> Vec<&str> {
+        h.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn writes_keep_previous_state_as_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+
+        with_history(&state_file, |h| h.push(entry("a.png", T0), 5)).unwrap();
+        with_history(&state_file, |h| h.push(entry("b.png", T0 + 1.0), 5)).unwrap();
+
+        let backup = std::fs::read_to_string(backup_file(&state_file)).unwrap();
+        assert_eq!(names(&parse_history(&backup).unwrap()), ["a.png"]);
+        assert!(!sibling(&state_file, ".tmp").exists());
+    }
+
+    #[test]
+    fn truncated_state_recovers_from_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+        with_history(&state_file, |h| h.push(entry("a.png", T0), 5)).unwrap();
+        with_history(&state_file, |h| h.push(entry("b.png", T0 + 1.0), 5)).unwrap();
+
+        // simulate a crash mid-write by an older version
+        let full = std::fs::read_to_string(&state_file).unwrap();
+        std::fs::write(&state_file, &full[..full.len() / 2]).unwrap();
+
+        assert_eq!(names(&read_history(&state_file)), ["a.png"]);
+
+        // the next write builds on the recovered state and keeps the backup
+        with_history(&state_file, |h| h.push(entry("c.png", T0 + 2.0), 5)).unwrap();
+        assert_eq!(names(&read_history(&state_file)), ["c.png", "a.png"]);
+        let backup = std::fs::read_to_string(backup_file(&state_file)).unwrap();
+        assert_eq!(names(&parse_history(&backup).unwrap()), ["a.png"]);
+    }
+
+    #[test]
+    fn leftover_temp_file_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+        with_history(&state_file, |h| h.push(entry("a.png", T0), 5)).unwrap();
+        std::fs::write(sibling(&state_file, ".tmp"), "{\"entr").unwrap();
+
+        assert_eq!(names(&read_history(&state_file)), ["a.png"]);
+        with_history(&state_file, |h| h.push(entry("b.png", T0), 5)).unwrap();
+        assert_eq!(names(&read_history(&state_file)), ["b.png", "a.png"]);
+    }
+
+    #[test]
+    fn with_history_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+        assert!(read_history(&state_file).entries.is_empty());
+
+        with_history(&state_file, |h| h.push(entry("a.png", T0), 5)).unwrap();
+        with_history(&state_file, |h| h.push(entry("b.png", T0 + 1.0), 5)).unwrap();
+
+        let h = read_history(&state_file);
+        let names: Vec<_> = h.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["b.png", "a.png"]);
+    }
+
+    #[test]
+    fn updates_that_change_nothing_leave_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+        with_history(&state_file, |h| h.push(entry("a.png", T0), 5)).unwrap();
+
+        let gone = Path::new("/tmp/other.png");
+        assert!(!update_history(&state_file, |h| h.mark_missing(gone)).unwrap());
+        assert!(!backup_file(&state_file).exists());
+
+        let a = Path::new("/tmp/a.png");
+        assert!(update_history(&state_file, |h| h.mark_missing(a)).unwrap());
+        assert!(read_history(&state_file).entries[0].missing);
+        assert!(backup_file(&state_file).exists());
+    }
+
+    #[test]
+    fn lock_times_out_while_held() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("glance-latest.json");
+        let held = FileLock::acquire(&state_file).unwrap();
+
+        let start = Instant::now();
+        let err = FileLock::acquire_timeout(&state_file, Duration::from_millis(50));
+        assert!(err.is_err());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        drop(held);
+        assert!(FileLock::acquire_timeout(&state_file, Duration::from_millis(50)).is_ok());
+    }
+
+    #[test]
+    fn renames_follow_and_deletes_mark_missing() {
+        let mut h = history(vec![entry("b.png", T0), entry("a.png", T0)]);
+        assert!(h.rename_path(Path::new("/tmp/a.png"), Path::new("/tmp/keep/a-final.png")));
+        assert_eq!(h.entries[1].name, "a-final.png");
+        assert_eq!(h.entries[1].path, Path::new("/tmp/keep/a-final.png"));
+        assert!(!h.rename_path(Path::new("/tmp/nope.png"), Path::new("/tmp/x.png")));
+
+        assert!(h.mark_missing(Path::new("/tmp/b.png")));
+        assert!(h.entries[0].missing);
+        assert!(!h.mark_missing(Path::new("/tmp/b.png")));
+        assert!(!h.entries[1].missing);
+    }
+
+    #[test]
+    fn paths_at_expands_batches_and_skips_bad_indices() {
+        let mut h = history(vec![entry("solo.png", T0)]);
+        h.push_grouped(arrival("/d", "a", T0 + 10.0), 2.0, 5);
+        h.push_grouped(arrival("/d", "b", T0 + 10.0), 2.0, 5);
+
+        assert_eq!(
+            h.paths_at(&[1, 0, 1, 7]),
+            [Path::new("/tmp/solo.png"), Path::new("/d/a"), Path::new("/d/b")]
+        );
+        assert!(h.paths_at(&[]).is_empty());
+    }
+
+    fn arrival(dir: &str, name: &str, time: f64) -> FileState {
+        FileState {
+            path: PathBuf::from(dir).join(name),
+            name: name.into(),
+            size: 10,
+            time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn burst_from_one_dir_becomes_a_batch() {
+        let mut h = history(vec![arrival("/home/u/Pictures", "older.png", T0 - 60.0)]);
+        for (i, name) in ["a.jpg", "b.jpg", "c.jpg"].iter().enumerate() {
+            h.push_grouped(arrival("/home/u/Downloads", name, T0 + i as f64 * 0.5), 2.0, 5);
+        }
+
+        assert_eq!(h.entries.len(), 2);
+        let batch = &h.entries[0];
+        assert!(batch.is_batch());
+        assert_eq!(batch.name, "3 files in Downloads");
+        assert_eq!(batch.path, Path::new("/home/u/Downloads"));
+        assert_eq!(batch.size, 30);
+        assert_eq!(batch.time, T0);
+        assert_eq!(
+            batch.paths(),
+            [
+                Path::new("/home/u/Downloads/a.jpg"),
+                Path::new("/home/u/Downloads/b.jpg"),
+                Path::new("/home/u/Downloads/c.jpg"),
+            ]
+        );
+        assert_eq!(h.entries[1].name, "older.png");
+    }
+
+    #[test]
+    fn grouping_needs_same_dir_and_window() {
+        let mut h = history(vec![]);
+        h.push_grouped(arrival("/d", "a", T0), 2.0, 5);
+        h.push_grouped(arrival("/e", "b", T0 + 0.1), 2.0, 5);
+        h.push_grouped(arrival("/e", "c", T0 + 5.0), 2.0, 5);
+        h.push_grouped(arrival("/e", "d", T0 + 5.1), 0.0, 5);
+        assert_eq!(names(&h), ["d", "c", "b", "a"]);
+
+        // the window runs from the batch's first file, not its latest
+        let mut h = history(vec![]);
+        for i in 0..4 {
+            h.push_grouped(arrival("/d", &format!("{i}"), T0 + i as f64), 2.0, 5);
+        }
+        assert_eq!(names(&h), ["3", "3 files in d"]);
+    }
+
+    #[test]
+    fn batch_members_follow_renames_and_deletes() {
+        let mut h = history(vec![]);
+        h.push_grouped(arrival("/d", "a", T0), 2.0, 5);
+        h.push_grouped(arrival("/d", "b", T0), 2.0, 5);
+
+        assert!(h.rename_path(Path::new("/d/a"), Path::new("/d/a2")));
+        assert_eq!(h.entries[0].paths(), [Path::new("/d/a2"), Path::new("/d/b")]);
+
+        assert!(h.mark_missing(Path::new("/d/b")));
+        assert_eq!(h.entries[0].name, "1 file in d");
+        assert!(!h.entries[0].missing);
+        assert!(h.mark_missing(Path::new("/d/a2")));
+        assert!(h.entries[0].missing);
+    }
 }
@@ -1,28 +1,31 @@
+use crate::clock::{Clock, SystemClock};
 use crate::config::Config;
-use crate::state::read_history;
-use crate::util::human_size;
+use crate::select::Selector;
+use crate::state::{read_history, HistoryState};
+use crate::util::{escape_markup, human_size};
 use anyhow::Result;
 use serde_json::json;
 
-pub fn run(cfg: &Config, index_override: Option<usize>) -> Result<()> {
-    let state_file = Config::state_file();
-    let history = read_history(&state_file);
-
-    // use override if provided, otherwise use persisted selection
-    let selected = index_override.unwrap_or(history.selected);
+fn expand_format(format: &str, name: &str, count: &str, remaining: &str) -> String {
+    format
+        .replace("{name}", name)
+        .replace("{count}", count)
+        .replace("{remaining}", remaining)
+}
 
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs_f64();
-    let recently_scrolled = history.last_scroll > 0.0
-        && (now - history.last_scroll) < cfg.dismiss_seconds as f64;
-    let manually_scrolled = selected != 0;
+/// Build the Waybar JSON for the persisted history state.
+pub fn format_status(cfg: &Config, index_override: Option<usize>, clock: &dyn Clock) -> String {
+    let history = read_history(&cfg.state_file());
+    render(cfg, &history, index_override, clock.now())
+}
 
-    let current = history
-        .entries
-        .get(selected)
-        .filter(|e| manually_scrolled || recently_scrolled || !e.is_expired(cfg.dismiss_seconds));
+fn render(cfg: &Config, history: &HistoryState, index_override: Option<usize>, now: f64) -> String {
+    // an explicit index is always shown; otherwise follow the dismiss rules
+    let selected = index_override.unwrap_or(history.selected);
+    let current = match index_override {
+        Some(i) => history.entries.get(i),
+        None => history.visible_entry(now, cfg),
+    };
 
     let active_count = history.entries.len();
 
@@ -38,20 +41,38 @@ pub fn run(cfg: &Config, index_override: Option<usize>) -> Result<()> {
             } else {
                 String::new()
             };
+            let remaining = history.remaining_secs(now, cfg).filter(|_| index_override.is_none());
+            let percentage = match remaining {
+                Some(r) if cfg.dismiss_seconds > 0 => {
+                    ((r / cfg.dismiss_seconds as f64) * 100.0).round().clamp(0.0, 100.0) as u8
+                }
+                Some(_) => 0,
+                None => 100,
+            };
+            let remaining_str = remaining
+                .map(|r| format!("{}s", r.ceil() as u64))
+                .unwrap_or_default();
             let tooltip_lines: Vec<String> = history
                 .entries
                 .iter()
                 .enumerate()
                 .map(|(i, e)| {
                     let marker = if i == selected { "▸" } else { " " };
-                    format!("{marker} {} ({})", e.name, human_size(e.size))
+                    let line = format!("{marker} {} ({})", escape_markup(&e.name), human_size(e.size));
+                    if e.missing {
+                        // greyed out: the file was deleted or moved away
+                        format!("<span fgalpha=\"50%\">{line} — missing</span>")
+                    } else {
+                        line
+                    }
                 })
                 .collect();
             json!({
-                "text": format!(" {name}{count_suffix}"),
+                "text": expand_format(&cfg.status_format, &name, &count_suffix, &remaining_str),
                 "tooltip": tooltip_lines.join("\n"),
                 "class": "active",
                 "alt": "active",
+                "percentage": percentage,
             })
         }
         None => json!({
@@ -59,9 +80,92 @@ pub fn run(cfg: &Config, index_override: Option<usize>) -> Result<()> {
             "tooltip": "",
             "class": "empty",
             "alt": "empty",
+            "percentage": 0,
         }),
     };
-    println!("{}", serde_json::to_string(&output)?);
+    serde_json::to_string(&output).unwrap()
+}
+
+pub fn run(cfg: &Config, sel: &Selector) -> Result<()> {
+    let history = read_history(&cfg.state_file());
+    let index = match sel.pick(&history) {
+        Ok(index) => index,
+        // a module pinned to an index past the end of history shows empty
+        Err(_) if sel.index.is_some() => sel.index,
+        Err(e) => return Err(e),
+    };
+    println!("{}", render(cfg, &history, index, SystemClock.now()));
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::FileState;
+    use std::path::PathBuf;
+
+    fn parse(json: &str) -> serde_json::Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn history_at(time: f64) -> HistoryState {
+        HistoryState {
+            entries: vec![FileState {
+                path: PathBuf::from("/tmp/shot.png"),
+                name: "shot.png".into(),
+                size: 2048,
+                time,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn counts_down_while_fresh() {
+        let cfg = Config {
+            dismiss_seconds: 10,
+            status_format: "{name} {remaining}".into(),
+            ..Default::default()
+        };
+        let out = parse(&render(&cfg, &history_at(100.0), None, 103.0));
+        assert_eq!(out["class"], "active");
+        assert_eq!(out["percentage"], 70);
+        assert_eq!(out["text"], "shot.png 7s");
+    }
+
+    #[test]
+    fn empty_once_expired() {
+        let cfg = Config { dismiss_seconds: 10, ..Default::default() };
+        let out = parse(&render(&cfg, &history_at(100.0), None, 200.0));
+        assert_eq!(out["class"], "empty");
+        assert_eq!(out["percentage"], 0);
+    }
+
+    #[test]
+    fn explicit_index_ignores_dismiss_timer() {
+        let cfg = Config { dismiss_seconds: 10, ..Default::default() };
+        let out = parse(&render(&cfg, &history_at(100.0), Some(0), 200.0));
+        assert_eq!(out["class"], "active");
+        assert_eq!(out["percentage"], 100);
+
+        let out = parse(&render(&cfg, &history_at(100.0), Some(3), 200.0));
+        assert_eq!(out["class"], "empty");
+    }
+
+    #[test]
+    fn tooltip_escapes_names_and_greys_out_missing_files() {
+        let cfg = Config::default();
+        let mut history = history_at(100.0);
+        history.entries.push(FileState {
+            path: PathBuf::from("/tmp/a&b.png"),
+            name: "a&b.png".into(),
+            missing: true,
+            ..Default::default()
+        });
+        let out = parse(&render(&cfg, &history, None, 101.0));
+        let tooltip = out["tooltip"].as_str().unwrap();
+        assert!(tooltip.starts_with("▸ shot.png (2.0 KB)\n"));
+        assert!(tooltip.contains("<span fgalpha=\"50%\">  a&amp;b.png (0 B) — missing</span>"));
+    }
+}
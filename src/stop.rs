@@ -0,0 +1,11 @@
+use crate::config::Config;
+use crate::instance;
+use anyhow::Result;
+
+pub fn run(cfg: &Config) -> Result<()> {
+    match instance::stop(cfg)? {
+        Some(pid) => println!("stopped glance watch (pid {pid})"),
+        None => println!("glance watch is not running"),
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Just enough of the sd_notify(3) protocol for a `Type=notify` user unit,
+//! without linking libsystemd.
+
+use std::ffi::OsStr;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::{Duration, Instant};
+
+/// Send a state string such as `READY=1` to the service manager. A no-op
+/// when not started by systemd.
+pub fn notify(state: &str) {
+    if let Some(socket) = std::env::var_os("NOTIFY_SOCKET") {
+        notify_to(&socket, state);
+    }
+}
+
+/// Send `state` to the socket named like `$NOTIFY_SOCKET`.
+fn notify_to(socket: &OsStr, state: &str) {
+    let Ok(sock) = UnixDatagram::unbound() else {
+        return;
+    };
+    let path = socket.as_bytes();
+    // a leading '@' names a socket in the abstract namespace
+    let sent = match path.strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)
+            .and_then(|addr| sock.send_to_addr(state.as_bytes(), &addr)),
+        None => sock.send_to(state.as_bytes(), socket),
+    };
+    if let Err(e) = sent {
+        crate::log::warn!("sd_notify {state:?} failed: {e}");
+    }
+}
+
+/// Tell the service manager a reload has started; follow up with `READY=1`.
+pub fn reloading() {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    let usec = ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000;
+    notify(&format!("RELOADING=1\nMONOTONIC_USEC={usec}"));
+}
+
+/// Pings the service manager's watchdog often enough to keep `WatchdogSec=`
+/// happy, if it's enabled for this process.
+pub struct Watchdog {
+    every: Duration,
+    last: Option<Instant>,
+}
+
+impl Watchdog {
+    pub fn from_env() -> Option<Self> {
+        let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+            if pid.parse() != Ok(std::process::id()) {
+                return None;
+            }
+        }
+        // half the timeout, as sd_watchdog_enabled(3) recommends
+        let every = Duration::from_micros(usec) / 2;
+        (!every.is_zero()).then_some(Self { every, last: None })
+    }
+
+    pub fn tick(&mut self) {
+        if self.last.is_none_or(|t| t.elapsed() >= self.every) {
+            notify("WATCHDOG=1");
+            self.last = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_sends_a_datagram_to_notify_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let listener = UnixDatagram::bind(&path).unwrap();
+        notify_to(path.as_os_str(), "READY=1");
+
+        let mut buf = [0u8; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
@@ -0,0 +1,383 @@
+//! Previews from the freedesktop thumbnail cache.
+//!
+//! Follows the Thumbnail Managing Standard: a file's thumbnail lives in
+//! `$XDG_CACHE_HOME/thumbnails/{normal,large}/<md5 of its URI>.png` and is
+//! only valid while its `Thumb::MTime` matches the file. Missing ones are
+//! made with whatever `*.thumbnailer` entries are installed (ffmpegthumbnailer,
+//! evince, ...), so file managers and glance share the work. Images are
+//! scaled straight from the file instead, with the result kept in
+//! `$XDG_CACHE_HOME/glance`.
+
+use crate::log;
+use crate::runner;
+use crate::util::{is_image, remove_stale};
+use gtk4::gdk_pixbuf::Pixbuf;
+use gtk4::gio::{self, prelude::*};
+use gtk4::glib::{self, ChecksumType};
+use std::fs::{self, File};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Once, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Scaled images nobody has looked at for this long are dropped.
+const SCALED_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    /// 128px
+    Normal,
+    /// 256px
+    Large,
+}
+
+impl Size {
+    /// The smallest cache size that covers `px`.
+    pub fn for_px(px: i32) -> Self {
+        if px <= 128 {
+            Size::Normal
+        } else {
+            Size::Large
+        }
+    }
+
+    fn px(self) -> u32 {
+        match self {
+            Size::Normal => 128,
+            Size::Large => 256,
+        }
+    }
+
+    fn dir(self) -> &'static str {
+        match self {
+            Size::Normal => "normal",
+            Size::Large => "large",
+        }
+    }
+}
+
+/// `file://` URI as GLib writes it, which is what the cache is keyed on.
+pub fn file_uri(path: &Path) -> String {
+    gio::File::for_path(path).uri().into()
+}
+
+fn md5(text: &str) -> String {
+    glib::compute_checksum_for_string(ChecksumType::Md5, text)
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+/// Where the thumbnail for `uri` lives under `root`.
+fn cache_path(root: &Path, uri: &str, size: Size) -> PathBuf {
+    root.join(size.dir()).join(format!("{}.png", md5(uri)))
+}
+
+/// What glance's own copy of an image is keyed on: the file's path and
+/// mtime, so an edited file gets a fresh one.
+fn scaled_key(path: &Path, mtime: u64) -> String {
+    format!("{}\n{mtime}", path.display())
+}
+
+/// Where glance keeps its own `px`-sized copy of an image.
+fn scaled_path(root: &Path, path: &Path, mtime: u64, px: i32) -> PathBuf {
+    root.join(format!("{}-{px}.png", md5(&scaled_key(path, mtime))))
+}
+
+fn mtime(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// A cached thumbnail for `path` that is still current, from the shared
+/// cache under `cache` (`Config::cache_dir`).
+pub fn lookup(cache: &Path, path: &Path, size: Size) -> Option<PathBuf> {
+    let root = cache.join("thumbnails");
+    if !root.is_dir() {
+        return None;
+    }
+    let mtime = mtime(path)?.to_string();
+    let uri = file_uri(path);
+    // a large thumbnail serves a normal-sized request too
+    let sizes: &[Size] = match size {
+        Size::Normal => &[Size::Normal, Size::Large],
+        Size::Large => &[Size::Large],
+    };
+    sizes.iter().find_map(|&size| {
+        let thumb = cache_path(&root, &uri, size);
+        let pixbuf = Pixbuf::from_file(&thumb).ok()?;
+        let field = |k: &str| pixbuf.option(k).map(String::from);
+        let current = field("tEXt::Thumb::URI") == Some(uri.clone())
+            && field("tEXt::Thumb::MTime") == Some(mtime.clone());
+        current.then_some(thumb)
+    })
+}
+
+/// An installed `*.thumbnailer` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Thumbnailer {
+    try_exec: Option<String>,
+    exec: String,
+    mime_types: Vec<String>,
+}
+
+fn parse_thumbnailer(text: &str) -> Option<Thumbnailer> {
+    let mut in_entry = false;
+    let (mut try_exec, mut exec, mut mime_types) = (None, None, Vec::new());
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Thumbnailer Entry]";
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_entry) else {
+            continue;
+        };
+        match key.trim() {
+            "TryExec" => try_exec = Some(value.trim().to_string()),
+            "Exec" => exec = Some(value.trim().to_string()),
+            "MimeType" => {
+                mime_types = value
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|m| !m.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    let exec = exec.filter(|e: &String| !e.is_empty())?;
+    Some(Thumbnailer { try_exec, exec, mime_types })
+}
+
+/// `$XDG_DATA_HOME` first, so user entries override system ones.
+fn thumbnailer_dirs() -> Vec<PathBuf> {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
+    let data_home = std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(&home).join(".local/share"));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    std::iter::once(data_home)
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|d| d.join("thumbnailers"))
+        .collect()
+}
+
+fn thumbnailers() -> &'static [Thumbnailer] {
+    static ALL: OnceLock<Vec<Thumbnailer>> = OnceLock::new();
+    ALL.get_or_init(|| {
+        let mut all = Vec::new();
+        for dir in thumbnailer_dirs() {
+            let Ok(entries) = fs::read_dir(&dir) else { continue };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "thumbnailer") {
+                    match fs::read_to_string(&path).ok().and_then(|t| parse_thumbnailer(&t)) {
+                        Some(t) => all.push(t),
+                        None => log::debug!("unreadable thumbnailer"; path = path.display()),
+                    }
+                }
+            }
+        }
+        all
+    })
+}
+
+/// The Exec line's arguments with `%i` (input path), `%u` (input URI),
+/// `%o` (output path) and `%s` (size) filled in.
+fn exec_args(exec: &str, input: &Path, uri: &str, output: &Path, size: u32) -> Vec<String> {
+    exec.split_whitespace()
+        .map(|word| {
+            let mut arg = String::new();
+            let mut chars = word.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    arg.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('i') => arg.push_str(&input.to_string_lossy()),
+                    Some('u') => arg.push_str(uri),
+                    Some('o') => arg.push_str(&output.to_string_lossy()),
+                    Some('s') => arg.push_str(&size.to_string()),
+                    Some(other) => arg.push(other),
+                    None => arg.push('%'),
+                }
+            }
+            arg
+        })
+        .collect()
+}
+
+pub fn mime_type(path: &Path) -> String {
+    let (guess, _) = gio::content_type_guess(Some(path), None);
+    gio::content_type_get_mime_type(&guess)
+        .map(|m| m.to_string())
+        .unwrap_or_else(|| guess.to_string())
+}
+
+/// Make a thumbnail for `path` with an installed thumbnailer and store it
+/// in the cache. None if no thumbnailer handles its type or it failed.
+pub fn generate(cache: &Path, path: &Path, size: Size) -> Option<PathBuf> {
+    let mtime = mtime(path)?;
+    let mime = mime_type(path);
+    let thumbnailer = thumbnailers().iter().find(|t| {
+        t.mime_types.contains(&mime)
+            && t.try_exec
+                .as_deref()
+                .or_else(|| t.exec.split_whitespace().next())
+                .is_some_and(runner::installed)
+    })?;
+
+    let uri = file_uri(path);
+    let dest = cache_path(&cache.join("thumbnails"), &uri, size);
+    let dir = dest.parent()?;
+    if let Err(e) = fs::DirBuilder::new().recursive(true).mode(0o700).create(dir) {
+        log::warn!("failed to create thumbnail cache: {e}"; path = dir.display());
+        return None;
+    }
+    // several previews may be generating at once, even for the same file
+    static GENERATED: AtomicUsize = AtomicUsize::new(0);
+    let name = dest.file_name()?.to_string_lossy();
+    let n = GENERATED.fetch_add(1, Ordering::Relaxed);
+    let tmp = dir.join(format!(".glance-{}-{n}-{name}", std::process::id()));
+    let args = exec_args(&thumbnailer.exec, path, &uri, &tmp, size.px());
+    let (bin, rest) = args.split_first()?;
+    let ok = runner::output(Command::new(bin).args(rest)).is_ok_and(|o| o.status.success());
+    let pixbuf = Pixbuf::from_file(&tmp).ok().filter(|_| ok);
+    let Some(pixbuf) = pixbuf else {
+        remove_stale(&tmp);
+        log::debug!("thumbnailer failed"; cmd = bin, path = path.display());
+        return None;
+    };
+
+    // written whole then renamed, so other readers never see half a file
+    let mtime = mtime.to_string();
+    let fields = [("tEXt::Thumb::URI", uri.as_str()), ("tEXt::Thumb::MTime", mtime.as_str())];
+    let written = pixbuf
+        .savev(&tmp, "png", &fields)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| Ok(fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?))
+        .and_then(|_| Ok(fs::rename(&tmp, &dest)?));
+    match written {
+        Ok(()) => Some(dest),
+        Err(e) => {
+            log::warn!("failed to save thumbnail: {e}"; path = dest.display());
+            remove_stale(&tmp);
+            None
+        }
+    }
+}
+
+/// Drop scaled copies that haven't been touched in a while. Once per process.
+fn prune_scaled(dir: &Path) {
+    static PRUNED: Once = Once::new();
+    PRUNED.call_once(|| {
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        let cutoff = SystemTime::now() - SCALED_MAX_AGE;
+        for entry in entries.flatten() {
+            let old = entry.metadata().and_then(|m| m.modified()).is_ok_and(|t| t < cutoff);
+            if old {
+                remove_stale(&entry.path());
+            }
+        }
+    });
+}
+
+fn save_scaled(pixbuf: &Pixbuf, dest: &Path) -> bool {
+    // the same image may be scaled by two jobs at once
+    static SAVES: AtomicUsize = AtomicUsize::new(0);
+    let Some(dir) = dest.parent() else { return false };
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let n = SAVES.fetch_add(1, Ordering::Relaxed);
+    let tmp = dir.join(format!(".{name}.{}.{n}", std::process::id()));
+    let saved = fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| pixbuf.savev(&tmp, "png", &[]).map_err(anyhow::Error::from))
+        .and_then(|_| fs::rename(&tmp, dest).map_err(anyhow::Error::from));
+    prune_scaled(dir);
+    match saved {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("failed to cache preview: {e}"; path = dest.display());
+            remove_stale(&tmp);
+            false
+        }
+    }
+}
+
+/// A small image file previewing `path` at up to `max` px: a current
+/// thumbnail from the shared cache, a thumbnailer's output, or for images a
+/// scaled-down copy (decoded at reduced size and kept for next time). Slow
+/// on a cold cache, so call it off the main thread.
+pub fn prepare(cache: &Path, path: &Path, max: i32) -> Option<PathBuf> {
+    let size = Size::for_px(max);
+    if let Some(thumb) = lookup(cache, path, size) {
+        return Some(thumb);
+    }
+    if !is_image(path) {
+        return generate(cache, path, size);
+    }
+
+    let cached = scaled_path(&cache.join("glance"), path, mtime(path)?, max);
+    if cached.exists() {
+        // keeps it from being pruned while it's still in use
+        let touched = File::options()
+            .write(true)
+            .open(&cached)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+        if let Err(e) = touched {
+            log::debug!("failed to touch cached preview: {e}"; path = cached.display());
+        }
+        return Some(cached);
+    }
+    let pixbuf = Pixbuf::from_file_at_scale(path, max, max, true).ok()?;
+    save_scaled(&pixbuf, &cached).then_some(cached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_copies_change_with_mtime() {
+        let a = scaled_key(Path::new("/s/a.png"), 100);
+        assert_eq!(a, scaled_key(Path::new("/s/a.png"), 100));
+        assert_ne!(a, scaled_key(Path::new("/s/a.png"), 101));
+        assert_ne!(a, scaled_key(Path::new("/s/b.png"), 100));
+    }
+
+    #[test]
+    fn thumbnailer_entries_parse() {
+        let t = parse_thumbnailer(
+            "[Thumbnailer Entry]\n\
+             TryExec=ffmpegthumbnailer\n\
+             Exec=ffmpegthumbnailer -i %i -o %o -s %s -f\n\
+             MimeType=video/mp4;video/webm;\n",
+        )
+        .unwrap();
+        assert_eq!(t.try_exec.as_deref(), Some("ffmpegthumbnailer"));
+        assert_eq!(t.mime_types, ["video/mp4", "video/webm"]);
+        assert!(parse_thumbnailer("[Desktop Entry]\nExec=foo\n").is_none());
+        assert!(parse_thumbnailer("[Thumbnailer Entry]\nExec=\nMimeType=video/mp4\n").is_none());
+    }
+
+    #[test]
+    fn exec_placeholders_are_filled_in() {
+        let args = exec_args(
+            "evince-thumbnailer -s %s %u %o --x=100%%",
+            Path::new("/d/a.pdf"),
+            "file:///d/a.pdf",
+            Path::new("/c/t.png"),
+            256,
+        );
+        assert_eq!(args, ["evince-thumbnailer", "-s", "256", "file:///d/a.pdf", "/c/t.png", "--x=100%"]);
+    }
+}
@@ -0,0 +1,342 @@
+//! Moving history entries to the trash, per the freedesktop Trash spec.
+//!
+//! Files on the home filesystem go to `$XDG_DATA_HOME/Trash`; files on other
+//! mounts go to that mount's `.Trash/$uid` (if the admin set one up) or
+//! `.Trash-$uid`, so trashing never copies data across devices. Each file gets
+//! a `.trashinfo` record, which is what lets file managers restore it.
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::log;
+use crate::select::Selector;
+use crate::state::{read_history, with_history, FileState};
+use crate::util::{existing_paths, remove_stale, signal_waybar};
+use crate::watch::expect_arrivals;
+use anyhow::{Context, Result};
+use gtk4::glib;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, DirBuilder, File};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// A file now in the trash, and where it came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trashed {
+    pub original: PathBuf,
+    /// The file itself, under `files/`
+    pub file: PathBuf,
+    /// Its record under `info/`
+    pub info: PathBuf,
+}
+
+/// What `glance trash --undo` (or the menu's Undo) puts back: the files, and
+/// the history entry they were, at its old position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Undo {
+    pub index: usize,
+    pub entry: FileState,
+    pub files: Vec<Trashed>,
+}
+
+fn home_trash() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
+    std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(home).join(".local/share"))
+        .join("Trash")
+}
+
+fn device(path: &Path) -> Option<u64> {
+    fs::symlink_metadata(path).ok().map(|m| m.dev())
+}
+
+/// The mount point `path` lives under.
+fn topdir(path: &Path) -> PathBuf {
+    let dev = device(path);
+    let mut top = path.to_path_buf();
+    while let Some(parent) = top.parent() {
+        if device(parent) != dev {
+            break;
+        }
+        top = parent.to_path_buf();
+    }
+    top
+}
+
+/// The trash directory for `path`, and the directory its `.trashinfo` Path
+/// is relative to (None: the home trash, which records absolute paths).
+fn trash_dir_for(path: &Path) -> (PathBuf, Option<PathBuf>) {
+    let home = home_trash();
+    let home_dev = device(&home).or_else(|| device(home.parent()?));
+    if home_dev.is_some() && home_dev == device(path) {
+        return (home, None);
+    }
+    let top = topdir(path);
+    let uid = unsafe { libc::getuid() };
+    // an admin-made shared .Trash must be a real, sticky directory
+    let shared = top.join(".Trash");
+    let usable = fs::symlink_metadata(&shared).is_ok_and(|m| m.is_dir() && m.mode() & 0o1000 != 0);
+    if usable {
+        (shared.join(uid.to_string()), Some(top))
+    } else {
+        (top.join(format!(".Trash-{uid}")), Some(top))
+    }
+}
+
+/// Local time as the spec wants it, e.g. `2024-05-01T12:30:00`.
+fn deletion_date() -> Result<String> {
+    Ok(glib::DateTime::now_local()?.format("%Y-%m-%dT%H:%M:%S")?.into())
+}
+
+/// `shot.png` → `shot.2.png`, for a name already taken in the trash.
+fn numbered(name: &str, n: u32) -> String {
+    match name.rfind('.').filter(|&i| i > 0) {
+        Some(i) => format!("{}.{n}{}", &name[..i], &name[i..]),
+        None => format!("{name}.{n}"),
+    }
+}
+
+/// `path` percent-encoded for a `.trashinfo` Path line, as GLib does.
+fn escape_path(path: &Path) -> String {
+    glib::Uri::escape_bytes(path.as_os_str().as_encoded_bytes(), Some("!$&'()*+,:=@/")).into()
+}
+
+/// Move `path` into the trash directory `dir`, recording it as `recorded`
+/// (already escaped).
+fn trash_into(path: &Path, dir: &Path, recorded: &str, date: &str) -> Result<Trashed> {
+    let (files, info) = (dir.join("files"), dir.join("info"));
+    for d in [&files, &info] {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(d)
+            .with_context(|| format!("can't create {}", d.display()))?;
+    }
+    let name = path
+        .file_name()
+        .context("nothing to trash")?
+        .to_string_lossy()
+        .into_owned();
+
+    // claiming the .trashinfo name first is what makes it ours
+    for n in 1.. {
+        let candidate = if n == 1 { name.clone() } else { numbered(&name, n) };
+        let info_path = info.join(format!("{candidate}.trashinfo"));
+        let file_path = files.join(&candidate);
+        let mut record = match File::options().write(true).create_new(true).open(&info_path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).context(format!("can't write {}", info_path.display())),
+        };
+        if file_path.exists() {
+            remove_stale(&info_path);
+            continue;
+        }
+        let moved = write!(
+            record,
+            "[Trash Info]\nPath={recorded}\nDeletionDate={date}\n"
+        )
+        .and_then(|_| fs::rename(path, &file_path));
+        if let Err(e) = moved {
+            remove_stale(&info_path);
+            return Err(e).context(format!("can't trash {}", path.display()));
+        }
+        return Ok(Trashed { original: path.to_path_buf(), file: file_path, info: info_path });
+    }
+    unreachable!()
+}
+
+/// Move one file to the trash.
+pub fn trash(path: &Path) -> Result<Trashed> {
+    let (dir, top) = trash_dir_for(path);
+    let recorded = match &top {
+        Some(top) => path.strip_prefix(top).unwrap_or(path),
+        None => path,
+    };
+    trash_into(path, &dir, &escape_path(recorded), &deletion_date()?)
+}
+
+/// Put a trashed file back where it was.
+pub fn restore(t: &Trashed) -> Result<()> {
+    if t.original.exists() {
+        anyhow::bail!("{} already exists", t.original.display());
+    }
+    fs::rename(&t.file, &t.original)
+        .with_context(|| format!("can't restore {}", t.original.display()))?;
+    remove_stale(&t.info);
+    Ok(())
+}
+
+/// Trash an entry's files and take it out of history. If only some of them
+/// could be trashed, the entry stays with just those marked gone.
+pub fn trash_entry(cfg: &Config, entry: &FileState) -> Result<Undo> {
+    let paths = existing_paths(&entry.paths());
+    if paths.is_empty() {
+        anyhow::bail!("{} is already gone", entry.name);
+    }
+    let mut files = Vec::new();
+    for p in &paths {
+        match trash(p) {
+            Ok(t) => files.push(t),
+            // don't strand the ones already trashed without an undo
+            Err(e) if !files.is_empty() => log::warn!("{e:#}"),
+            Err(e) => return Err(e),
+        }
+    }
+    let mut index = 0;
+    with_history(&cfg.state_file(), |h| {
+        if files.len() < paths.len() {
+            for t in &files {
+                h.mark_missing(&t.original);
+            }
+            index = h.entries.iter().position(|e| e.same_entry(entry)).unwrap_or(0);
+        } else if let Some((i, _)) = h.remove(entry) {
+            index = i;
+        }
+    })?;
+    signal_waybar(cfg.signal_number);
+    log::info!("trashed"; path = entry.path.display(), count = files.len());
+    Ok(Undo { index, entry: entry.clone(), files })
+}
+
+/// Restore the files, and their history entry as far as they came back.
+pub fn undo(cfg: &Config, undo: &Undo) -> Result<()> {
+    // the watcher takes the files coming back as the old entry, not news
+    let originals: Vec<PathBuf> = undo.files.iter().map(|t| t.original.clone()).collect();
+    expect_arrivals(cfg, &originals);
+    let mut failed = None;
+    let mut back = Vec::new();
+    for t in &undo.files {
+        match restore(t) {
+            Ok(()) => back.push(t.original.as_path()),
+            Err(e) => {
+                log::warn!("{e:#}");
+                failed = Some(e);
+            }
+        }
+    }
+    if !back.is_empty() {
+        let mut entry = undo.entry.clone();
+        if entry.is_batch() {
+            entry.files.retain(|f| back.contains(&f.as_path()));
+            entry.rename_batch();
+        }
+        with_history(&cfg.state_file(), |h| {
+            let path = entry.path.clone();
+            if !h.restore(undo.index, entry) {
+                log::info!("already back in history"; path = path.display());
+            }
+        })?;
+    }
+    signal_waybar(cfg.signal_number);
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Keep `undo` for a later `glance trash --undo`; only the last one is kept.
+pub fn remember(cfg: &Config, undo: &Undo) {
+    let path = cfg.trash_undo_file();
+    let saved = serde_json::to_string(undo)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(fs::write(&path, json)?));
+    if let Err(e) = saved {
+        log::warn!("failed to save undo record: {e}"; path = path.display());
+    }
+}
+
+pub fn run(cfg: &Config, sel: &Selector, undo_last: bool) -> Result<()> {
+    let undo_file = cfg.trash_undo_file();
+    if undo_last {
+        let text = fs::read_to_string(&undo_file).context("nothing to undo")?;
+        let last: Undo = serde_json::from_str(&text)?;
+        undo(cfg, &last)?;
+        remove_stale(&undo_file);
+        println!("restored {}", last.entry.name);
+        return Ok(());
+    }
+
+    let history = read_history(&cfg.state_file());
+    let Some(entry) = sel.target(&history, SystemClock.now(), cfg)? else {
+        return Ok(());
+    };
+    let done = trash_entry(cfg, entry)?;
+    remember(cfg, &done);
+    println!("moved {} to the trash (undo: glance trash --undo)", entry.name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taken_names_get_a_number_before_the_extension() {
+        assert_eq!(numbered("shot.png", 2), "shot.2.png");
+        assert_eq!(numbered("archive.tar.gz", 3), "archive.tar.3.gz");
+        assert_eq!(numbered("README", 2), "README.2");
+        assert_eq!(numbered(".bashrc", 2), ".bashrc.2");
+    }
+
+    #[test]
+    fn trashed_files_are_recorded_and_restorable() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("Trash");
+        let shot = dir.path().join("my shot.png");
+        let recorded = "/home/me/my%20shot.png";
+
+        fs::write(&shot, "one").unwrap();
+        let first = trash_into(&shot, &trash_dir, recorded, "2024-05-01T12:30:00").unwrap();
+        assert!(!shot.exists());
+        assert_eq!(first.file, trash_dir.join("files/my shot.png"));
+        let info = fs::read_to_string(&first.info).unwrap();
+        assert_eq!(
+            info,
+            "[Trash Info]\nPath=/home/me/my%20shot.png\nDeletionDate=2024-05-01T12:30:00\n"
+        );
+
+        // same name again: both kept
+        fs::write(&shot, "two").unwrap();
+        let second = trash_into(&shot, &trash_dir, recorded, "2024-05-01T12:31:00").unwrap();
+        assert_eq!(second.file, trash_dir.join("files/my shot.2.png"));
+        assert_eq!(second.info, trash_dir.join("info/my shot.2.png.trashinfo"));
+
+        restore(&second).unwrap();
+        assert_eq!(fs::read_to_string(&shot).unwrap(), "two");
+        assert!(!second.info.exists());
+        // the other one can't go back on top of it
+        assert!(restore(&first).is_err());
+        assert!(first.file.exists());
+    }
+
+    #[test]
+    fn undo_brings_back_only_what_was_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = Config { runtime_dir: dir.path().to_path_buf(), ..Config::default() };
+        let trash_dir = dir.path().join("Trash");
+        let (a, b) = (dir.path().join("a.png"), dir.path().join("b.png"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let files = vec![
+            trash_into(&a, &trash_dir, "a.png", "2024-05-01T12:30:00").unwrap(),
+            trash_into(&b, &trash_dir, "b.png", "2024-05-01T12:30:00").unwrap(),
+        ];
+        let entry = FileState {
+            path: dir.path().to_path_buf(),
+            name: "2 files in x".into(),
+            files: vec![a.clone(), b.clone()],
+            ..Default::default()
+        };
+        // something new took b's place meanwhile
+        fs::write(&b, "new").unwrap();
+
+        assert!(undo(&cfg, &Undo { index: 0, entry, files }).is_err());
+        let history = read_history(&cfg.state_file());
+        assert_eq!(history.entries[0].files, vec![a.clone()]);
+        assert_eq!(history.entries[0].name.split(' ').next(), Some("1"));
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new");
+    }
+}
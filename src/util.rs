@@ -1,7 +1,129 @@
+use crate::log;
+use crate::runner;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// Poke the main waybar process so it re-runs `glance status`.
+pub fn signal_waybar(sig: u8) {
+    let result = runner::output(
+        Command::new("pkill")
+            .arg(format!("-RTMIN+{sig}"))
+            .arg("-x")
+            .arg("-o")
+            .arg("waybar"),
+    );
+    match result {
+        // pkill exits 1 when nothing matched: waybar just isn't running
+        Ok(out) if !out.status.success() => log::debug!("no waybar to signal"; signal = sig),
+        Ok(_) => {}
+        Err(e) => log::warn!("failed to run pkill: {e}"),
+    }
+}
+
+/// Shell-style glob match of a whole file name: `*` any run, `?` one
+/// character, `[abc]` / `[a-z]` / `[!a-z]` a set.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    // backtrack to the last `*` on mismatch
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() {
+            match p[pi] {
+                '*' => {
+                    star = Some((pi, ti));
+                    pi += 1;
+                    continue;
+                }
+                '?' => {
+                    pi += 1;
+                    ti += 1;
+                    continue;
+                }
+                '[' => {
+                    if let Some((matched, len)) = match_class(&p[pi..], t[ti]) {
+                        if matched {
+                            pi += len;
+                            ti += 1;
+                            continue;
+                        }
+                    } else if t[ti] == '[' {
+                        // unterminated class: a literal '['
+                        pi += 1;
+                        ti += 1;
+                        continue;
+                    }
+                }
+                c if c == t[ti] => {
+                    pi += 1;
+                    ti += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match star {
+            Some((sp, st)) => {
+                pi = sp + 1;
+                ti = st + 1;
+                star = Some((sp, st + 1));
+            }
+            None => return false,
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the `[...]` class at the start of `p`. Returns whether
+/// it matched and the class's length, or None if the class is unterminated.
+fn match_class(p: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(p.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < p.len() {
+        if p[i] == ']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
+        if p.get(i + 1) == Some(&'-') && p.get(i + 2).is_some_and(|&e| e != ']') {
+            matched |= p[i] <= c && c <= p[i + 2];
+            i += 3;
+        } else {
+            matched |= p[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Print for scripts: a reader that goes away early (`glance list | head`)
+/// ends output quietly instead of panicking like `println!`.
+pub fn print_stdout(text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut out = std::io::stdout().lock();
+    match out.write_all(text.as_bytes()).and_then(|_| out.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+/// Remove a runtime file that may well not exist; anything other than
+/// "not found" is logged.
+pub fn remove_stale(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("failed to remove {}: {e}", path.display());
+        }
+    }
+}
+
 pub fn cursor_pos() -> Option<(i32, i32)> {
-    let out = Command::new("hyprctl").arg("cursorpos").output().ok()?;
+    let out = runner::output(Command::new("hyprctl").arg("cursorpos")).ok()?;
     let text = String::from_utf8(out.stdout).ok()?;
     let parts: Vec<&str> = text.trim().split(',').collect();
     if parts.len() >= 2 {
@@ -12,10 +134,7 @@ pub fn cursor_pos() -> Option<(i32, i32)> {
 }
 
 pub fn find_monitor_at(gx: i32, gy: i32) -> Option<(String, i32, i32)> {
-    let out = Command::new("hyprctl")
-        .args(["monitors", "-j"])
-        .output()
-        .ok()?;
+    let out = runner::output(Command::new("hyprctl").args(["monitors", "-j"])).ok()?;
     let text = String::from_utf8(out.stdout).ok()?;
     let monitors: Vec<serde_json::Value> = serde_json::from_str(&text).ok()?;
     for m in &monitors {
@@ -31,6 +150,36 @@ pub fn find_monitor_at(gx: i32, gy: i32) -> Option<(String, i32, i32)> {
     None
 }
 
+/// Escape text for Pango markup (Waybar tooltips, GTK labels).
+pub fn escape_markup(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The given paths that are still on disk; deleted files are silently dropped.
+pub fn existing_paths(paths: &[&Path]) -> Vec<PathBuf> {
+    paths.iter().filter(|p| p.exists()).map(|p| p.to_path_buf()).collect()
+}
+
+const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "svg", "tiff"];
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
 pub fn human_size(bytes: u64) -> String {
     let mut size = bytes as f64;
     for unit in &["B", "KB", "MB", "GB"] {
@@ -45,3 +194,32 @@ pub fn human_size(bytes: u64) -> String {
     }
     format!("{size:.1} TB")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_match("*.png", "shot.png"));
+        assert!(!glob_match("*.png", "shot.png.part"));
+        assert!(glob_match("invoice*", "invoice-2024.pdf"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+        assert!(glob_match("?.txt", "a.txt"));
+        assert!(!glob_match("?.txt", "ab.txt"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn glob_classes() {
+        assert!(glob_match("img[0-9].jpg", "img7.jpg"));
+        assert!(!glob_match("img[0-9].jpg", "imgx.jpg"));
+        assert!(glob_match("[!.]*", "visible"));
+        assert!(!glob_match("[!.]*", ".hidden"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("a[", "a["));
+    }
+}
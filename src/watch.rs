@@ -1,18 +1,45 @@
+use crate::clock::{Clock, SystemClock};
 use crate::config::Config;
-use crate::state::{with_history, FileState};
+use crate::instance::{running_pid, PidLock};
+use crate::log;
+use crate::rules::{self, Filed, Job};
+use crate::runner;
+use crate::state::{read_history, update_history, with_history, FileLock, FileState};
+use crate::systemd::{self, Watchdog};
+use crate::util::{remove_stale, signal_waybar};
 use anyhow::Result;
 use inotify::{EventMask, Inotify, WatchMask};
 use std::collections::{HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
 use std::os::fd::AsRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
 const MAX_SEEN: usize = 1000;
-const SEEN_TTL: u64 = 3600;
+const SEEN_TTL: f64 = 3600.0;
+/// Big enough for a few thousand events, so a burst of downloads doesn't
+/// take dozens of reads (or overflow the kernel queue while we catch up).
+const EVENT_BUF_SIZE: usize = 64 * 1024;
+/// A rescan also looks at files modified slightly before the newest one we
+/// announced, to cover coarse mtime granularity.
+const RESCAN_SLACK: f64 = 2.0;
+/// How long a MOVED_FROM waits for its MOVED_TO before we decide the file
+/// left the watched dirs.
+const MOVE_PAIR_TIMEOUT: f64 = 1.0;
+/// How long a path glance said it would put a file at stays expected.
+const EXPECT_TTL: f64 = 30.0;
+/// How often to check on files the rules thread is still filing.
+const FILING_POLL: Duration = Duration::from_millis(50);
+/// How often to look through history for files that an age rule
+/// (`min_age_days`) now applies to.
+const AGE_SWEEP_INTERVAL: f64 = 3600.0;
 
 struct SeenCache {
-    entries: VecDeque<(String, u64)>,
+    entries: VecDeque<(String, f64)>,
 }
 
 impl SeenCache {
@@ -22,8 +49,7 @@ impl SeenCache {
         }
     }
 
-    fn insert(&mut self, path: String) {
-        let now = now_secs();
+    fn insert(&mut self, path: String, now: f64) {
         // evict expired
         while let Some((_, ts)) = self.entries.front() {
             if now - ts > SEEN_TTL {
@@ -42,141 +68,678 @@ impl SeenCache {
     fn contains(&self, path: &str) -> bool {
         self.entries.iter().any(|(p, _)| p == path)
     }
-}
-
-fn now_secs() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
-}
 
-fn signal_waybar(sig: u8) {
-    let _ = Command::new("pkill")
-        .arg(format!("-RTMIN+{sig}"))
-        .arg("-x")
-        .arg("-o")
-        .arg("waybar")
-        .output();
-}
-
-fn menu_lock_exists() -> bool {
-    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
-    PathBuf::from(runtime).join("glance-menu.lock").exists()
+    fn remove(&mut self, path: &str) {
+        self.entries.retain(|(p, _)| p != path);
+    }
 }
 
 fn signal_dismiss(cfg: &Config) {
-    if menu_lock_exists() {
+    if cfg.menu_lock_file().exists() {
         return; // menu is open, don't dismiss
     }
     signal_waybar(cfg.signal_number);
     // clear cached menu position so it re-centers on next click
-    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
-    let _ = std::fs::remove_file(PathBuf::from(runtime).join("glance-menu-pos"));
+    remove_stale(&cfg.menu_pos_file());
 }
 
-pub fn run(cfg: &Config) -> Result<()> {
-    let pid_file = Config::pid_file();
-
-    std::fs::write(&pid_file, std::process::id().to_string())?;
-
-    // cleanup on ctrl-c / SIGTERM
-    let sig_num = cfg.signal_number;
-    ctrlc::set_handler(move || {
-        let _ = std::fs::remove_file(Config::pid_file());
-        signal_waybar(sig_num);
-        std::process::exit(0);
-    })?;
+/// The inotify side of `glance watch`, driven one [`Watcher::step`] at a time.
+pub struct Watcher {
+    cfg: Config,
+    clock: Arc<dyn Clock>,
+    inotify: Inotify,
+    wd_to_dir: HashMap<i32, PathBuf>,
+    seen: SeenCache,
+    dismiss_at: Option<f64>,
+    /// When to tell waybar about files that joined a batch, once its window
+    /// has closed.
+    pending_signal_at: Option<f64>,
+    /// MOVED_FROM events waiting for a matching MOVED_TO, keyed by cookie.
+    pending_moves: HashMap<u32, (PathBuf, f64)>,
+    /// Rule actions go to the rules thread so slow ones don't hold up the
+    /// loop; what became of each file comes back on `filed`.
+    jobs: Sender<Job>,
+    filed: Receiver<Filed>,
+    /// Jobs sent that haven't come back yet.
+    filing: usize,
+    /// When to next sweep history for age rules.
+    next_sweep: f64,
+    /// Newest mtime we've announced; a rescan picks up anything after it.
+    last_seen: f64,
+    buf: Vec<u8>,
+}
 
-    let mut inotify = Inotify::init()?;
-    let mut wd_to_dir: HashMap<i32, PathBuf> = HashMap::new();
+impl Watcher {
+    pub fn new(cfg: Config, clock: Arc<dyn Clock>) -> Result<Self> {
+        let (inotify, wd_to_dir) = add_watches(&cfg.watch_dirs)?;
+        let last_seen = clock.now();
+        let (jobs, filed) = spawn_rules_thread()?;
+        Ok(Self {
+            cfg,
+            clock,
+            inotify,
+            wd_to_dir,
+            seen: SeenCache::new(),
+            dismiss_at: None,
+            pending_signal_at: None,
+            pending_moves: HashMap::new(),
+            jobs,
+            filed,
+            filing: 0,
+            next_sweep: last_seen,
+            last_seen,
+            buf: vec![0u8; EVENT_BUF_SIZE],
+        })
+    }
 
-    for dir in &cfg.watch_dirs {
-        let path = PathBuf::from(shellexpand::tilde(dir).as_ref());
-        if path.is_dir() {
-            let wd = inotify.watches().add(
-                &path,
-                WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO,
-            )?;
-            wd_to_dir.insert(wd.get_watch_descriptor_id(), path.clone());
-            eprintln!("watching {}", path.display());
-        }
+    /// Number of directories actually being watched.
+    pub fn dir_count(&self) -> usize {
+        self.wd_to_dir.len()
     }
 
-    let mut seen = SeenCache::new();
-    let mut dismiss_at: Option<u64> = None;
-    let mut buf = [0u8; 4096];
+    /// Whether the rules thread is still at work on some arrivals.
+    pub fn filing(&self) -> bool {
+        self.filing > 0
+    }
 
-    // use poll(2) so we can wake up for dismiss timeout
-    let inotify_fd = inotify.as_raw_fd();
+    /// Switch to a new config, e.g. after SIGHUP. Watches are rebuilt from
+    /// scratch, and the new dirs are rescanned so nothing that landed in
+    /// between is lost.
+    pub fn reload(&mut self, cfg: Config) -> Result<()> {
+        let (inotify, wd_to_dir) = add_watches(&cfg.watch_dirs)?;
+        self.inotify = inotify;
+        self.wd_to_dir = wd_to_dir;
+        self.pending_moves.clear();
+        self.cfg = cfg;
+        self.rescan();
+        // new age rules get a look at history straight away
+        self.next_sweep = self.clock.now();
+        Ok(())
+    }
 
-    loop {
+    /// Announce what the rules thread has filed, sweep history for age rules
+    /// and fire the signal and dismiss timers if they're due, then wait up
+    /// to `timeout` for inotify events and handle them. Returns how many
+    /// events were read.
+    pub fn step(&mut self, timeout: Duration) -> Result<usize> {
+        self.collect_filed();
+        let now = self.clock.now();
+        if now >= self.next_sweep {
+            self.next_sweep = now + AGE_SWEEP_INTERVAL;
+            self.sweep_aged(now);
+        }
+        if self.pending_signal_at.is_some_and(|at| now >= at) {
+            self.pending_signal_at = None;
+            signal_waybar(self.cfg.signal_number);
+        }
         // check dismiss
-        if let Some(at) = dismiss_at {
-            if now_secs() >= at {
-                dismiss_at = None;
-                signal_dismiss(cfg);
+        if let Some(at) = self.dismiss_at {
+            if now >= at {
+                self.dismiss_at = None;
+                signal_dismiss(&self.cfg);
             }
         }
 
-        // poll with 1s timeout so we can check dismiss_at
+        self.expire_moves();
+
+        // don't sleep past a pending signal
+        let timeout = match self.pending_signal_at {
+            Some(at) => timeout.min(Duration::from_secs_f64((at - now).max(0.0))),
+            None => timeout,
+        };
+        // nor long past the rules thread finishing a file
+        let timeout = if self.filing() {
+            timeout.min(FILING_POLL)
+        } else {
+            timeout
+        };
+
+        // use poll(2) so we can wake up for dismiss timeout
         let mut pfd = libc::pollfd {
-            fd: inotify_fd,
+            fd: self.inotify.as_raw_fd(),
             events: libc::POLLIN,
             revents: 0,
         };
-        let ret = unsafe { libc::poll(&mut pfd as *mut _, 1, 1000) };
+        let ret = unsafe { libc::poll(&mut pfd as *mut _, 1, timeout.as_millis() as i32) };
         if ret <= 0 {
-            continue;
+            return Ok(0);
         }
 
-        let events = inotify.read_events(&mut buf)?;
-        for event in events {
-            if !event.mask.contains(EventMask::CLOSE_WRITE)
-                && !event.mask.contains(EventMask::MOVED_TO)
-            {
+        let events: Vec<(i32, EventMask, u32, Option<OsString>)> = self
+            .inotify
+            .read_events(&mut self.buf)?
+            .map(|e| {
+                let name = e.name.map(OsStr::to_owned);
+                (e.wd.get_watch_descriptor_id(), e.mask, e.cookie, name)
+            })
+            .collect();
+        let count = events.len();
+        let mut arrivals = Vec::new();
+        let mut overflowed = false;
+        for (wd, mask, cookie, name) in events {
+            if mask.contains(EventMask::Q_OVERFLOW) {
+                overflowed = true;
                 continue;
             }
-            let Some(name) = event.name else { continue };
-            let name_str = name.to_string_lossy();
+            let Some(name) = name else { continue };
+            let Some(dir) = self.wd_to_dir.get(&wd) else { continue };
+            let path = dir.join(&name);
 
-            if name_str.starts_with('.') {
-                continue;
+            if mask.contains(EventMask::MOVED_FROM) {
+                self.pending_moves.insert(cookie, (path, self.clock.now()));
+            } else if mask.contains(EventMask::MOVED_TO) {
+                // a rename of something we've already announced just
+                // updates its entry; anything else is a new arrival
+                let renamed = match self.pending_moves.remove(&cookie) {
+                    Some((from, _)) => {
+                        self.announce(std::mem::take(&mut arrivals));
+                        self.handle_rename(&from, &path)
+                    }
+                    None => false,
+                };
+                let expected = self.take_expected(&path);
+                if !renamed && !expected && self.accept(&path) {
+                    arrivals.push(path);
+                }
+            } else if mask.contains(EventMask::DELETE) {
+                self.announce(std::mem::take(&mut arrivals));
+                self.handle_gone(&path);
+            } else if mask.contains(EventMask::CLOSE_WRITE) && self.accept(&path) {
+                arrivals.push(path);
             }
-            if cfg
-                .ignore_suffixes
-                .iter()
-                .any(|s| name_str.ends_with(s.as_str()))
-            {
+        }
+        self.announce(arrivals);
+
+        if overflowed {
+            log::warn!("inotify queue overflowed, rescanning watched dirs");
+            self.rescan();
+        }
+        Ok(count)
+    }
+
+    /// Announce files that arrived while the watcher wasn't running: anything
+    /// newer than the last history entry, looking back at most
+    /// `startup_scan_seconds`.
+    pub fn catch_up(&mut self) {
+        let history = read_history(&self.cfg.state_file());
+        let floor = self.clock.now() - self.cfg.startup_scan_seconds as f64;
+        let since = history
+            .entries
+            .iter()
+            .map(|e| e.time)
+            .fold(floor, f64::max);
+        let known: Vec<&Path> = history.entries.iter().map(|e| e.path.as_path()).collect();
+        let candidates: Vec<PathBuf> = self
+            .scan_since(since)
+            .into_iter()
+            .filter(|p| !known.contains(&p.as_path()))
+            .collect();
+        let missed: Vec<PathBuf> = candidates.into_iter().filter(|p| self.accept(p)).collect();
+        if !missed.is_empty() {
+            log::info!("catching up on files from before startup"; count = missed.len());
+        }
+        self.announce(missed);
+    }
+
+    /// After an overflow the event stream has holes, so look at the
+    /// directories directly for anything newer than what we last announced.
+    fn rescan(&mut self) {
+        let candidates = self.scan_since(self.last_seen - RESCAN_SLACK);
+        let missed: Vec<PathBuf> = candidates.into_iter().filter(|p| self.accept(p)).collect();
+        self.announce(missed);
+    }
+
+    /// Files in the watched dirs modified after `since`, oldest first.
+    fn scan_since(&self, since: f64) -> Vec<PathBuf> {
+        let mut found: Vec<(f64, PathBuf)> = Vec::new();
+        for dir in self.wd_to_dir.values() {
+            let Ok(read_dir) = std::fs::read_dir(dir) else { continue };
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                if let Some(mtime) = mtime_secs(&path).filter(|m| *m > since) {
+                    found.push((mtime, path));
+                }
+            }
+        }
+        found.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        found.into_iter().map(|(_, p)| p).collect()
+    }
+
+    /// MOVED_FROMs that never got a MOVED_TO went somewhere we don't watch.
+    fn expire_moves(&mut self) {
+        let now = self.clock.now();
+        let expired: Vec<u32> = self
+            .pending_moves
+            .iter()
+            .filter(|(_, (_, at))| now - at >= MOVE_PAIR_TIMEOUT)
+            .map(|(cookie, _)| *cookie)
+            .collect();
+        for cookie in expired {
+            if let Some((path, _)) = self.pending_moves.remove(&cookie) {
+                // moved out and straight back, e.g. an undone trash
+                if !path.exists() {
+                    self.handle_gone(&path);
+                }
+            }
+        }
+    }
+
+    fn handle_rename(&mut self, from: &Path, to: &Path) -> bool {
+        let renamed = update_history(&self.cfg.state_file(), |h| h.rename_path(from, to))
+            .unwrap_or_else(|e| {
+                log::error!("failed to record rename: {e:#}"; from = from.display(), to = to.display());
+                false
+            });
+        if renamed {
+            self.seen.remove(&from.to_string_lossy());
+            self.seen.insert(to.to_string_lossy().into_owned(), self.clock.now());
+            signal_waybar(self.cfg.signal_number);
+            log::info!("renamed"; from = from.display(), to = to.display());
+        }
+        renamed
+    }
+
+    fn handle_gone(&mut self, path: &Path) {
+        // a new file at the same path later should be announced again
+        self.seen.remove(&path.to_string_lossy());
+        let changed = update_history(&self.cfg.state_file(), |h| h.mark_missing(path))
+            .unwrap_or_else(|e| {
+                log::error!("failed to mark file missing: {e:#}"; path = path.display());
+                false
+            });
+        if changed {
+            signal_waybar(self.cfg.signal_number);
+            log::info!("gone"; path = path.display());
+        }
+    }
+
+    /// Whether glance itself put a file at `path` (see [`expect_arrivals`]).
+    /// Each expectation is used up by the first arrival.
+    fn take_expected(&self, path: &Path) -> bool {
+        let file = self.cfg.expected_file();
+        if !file.exists() {
+            return false;
+        }
+        let now = self.clock.now();
+        let mut found = false;
+        let updated = update_expected(&file, |list| {
+            found = list.iter().any(|(p, until)| p == path && *until > now);
+            list.retain(|(p, until)| p != path && *until > now);
+        });
+        if let Err(e) = updated {
+            log::warn!("failed to update expected arrivals: {e:#}"; path = file.display());
+        }
+        found
+    }
+
+    /// Whether `path` is a new file worth announcing. Marks it as seen.
+    fn accept(&mut self, path: &Path) -> bool {
+        let name_str = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let ignored = |reason: &str| {
+            log::debug!("ignored"; reason = reason, path = path.display());
+            false
+        };
+        if name_str.starts_with('.') {
+            return ignored("dotfile");
+        }
+        if self
+            .cfg
+            .ignore_suffixes
+            .iter()
+            .any(|s| name_str.ends_with(s.as_str()))
+        {
+            return ignored("suffix");
+        }
+
+        if !path.is_file() {
+            return ignored("not a file");
+        }
+        let path_str = path.to_string_lossy().into_owned();
+        if self.seen.contains(&path_str) {
+            return ignored("seen");
+        }
+
+        self.seen.insert(path_str, self.clock.now());
+        true
+    }
+
+    /// Hand accepted files that a rule matches to the rules thread, and
+    /// record the rest.
+    fn announce(&mut self, paths: Vec<PathBuf>) {
+        let mut entries: Vec<FileState> = Vec::new();
+        for path in paths {
+            if let Some(mtime) = mtime_secs(&path) {
+                self.last_seen = self.last_seen.max(mtime);
+            }
+            if let Some(job) = rules::prepare(&self.cfg, &path, self.clock.now()) {
+                if self.dispatch(job) {
+                    continue;
+                }
+                log::error!("rules thread is gone, announcing as is"; path = path.display());
+            }
+            if let Ok(st) = FileState::new(path, &*self.clock) {
+                entries.push(st);
+            }
+        }
+        self.record(entries);
+    }
+
+    /// Hand `job` to the rules thread; false if the thread is gone.
+    fn dispatch(&mut self, job: Job) -> bool {
+        // what a rule writes is not news (and must not set it off again)
+        for p in job.created() {
+            self.seen.insert(p.to_string_lossy().into_owned(), self.clock.now());
+        }
+        if self.jobs.send(job).is_err() {
+            return false;
+        }
+        self.filing += 1;
+        true
+    }
+
+    /// Hand files in history that an age rule now matches to the rules
+    /// thread. An entry is marked swept once any of its files is, so the
+    /// rule doesn't act on it again each hour (or each restart).
+    fn sweep_aged(&mut self, now: f64) {
+        if !self.cfg.rules.iter().any(|r| r.min_age_days.is_some()) {
+            return;
+        }
+        let history = read_history(&self.cfg.state_file());
+        let mut swept: Vec<&FileState> = Vec::new();
+        for entry in history.entries.iter().filter(|e| !e.swept && !e.missing) {
+            let jobs: Vec<Job> = entry
+                .paths()
+                .into_iter()
+                .filter_map(|p| rules::prepare_aged(&self.cfg, p, now))
+                .collect();
+            if jobs.is_empty() {
                 continue;
             }
+            for job in jobs {
+                if !self.dispatch(job) {
+                    log::error!("rules thread is gone, not sweeping"; path = entry.path.display());
+                    break;
+                }
+            }
+            swept.push(entry);
+        }
+        if swept.is_empty() {
+            return;
+        }
+        log::info!("age rules matched files in history"; entries = swept.len());
+        let marked = with_history(&self.cfg.state_file(), |h| {
+            for e in h.entries.iter_mut().filter(|e| swept.iter().any(|s| s.same_entry(e))) {
+                e.swept = true;
+            }
+        });
+        if let Err(e) = marked {
+            log::error!("failed to record the age sweep: {e:#}");
+        }
+    }
 
-            let dir = match wd_to_dir.get(&event.wd.get_watch_descriptor_id()) {
-                Some(d) => d,
-                None => continue,
-            };
-            let path = dir.join(&*name_str);
+    /// Bring an entry already in history up to date with what an age rule
+    /// did to one of its files.
+    fn refile(&mut self, filed: Filed) {
+        let from = &filed.from;
+        let changed = update_history(&self.cfg.state_file(), |h| match &filed.path {
+            None => h.mark_missing(from),
+            Some(to) => {
+                let moved = to != from && h.rename_path(from, to);
+                let tagged = h.tag(to, &filed.tags);
+                moved || tagged
+            }
+        })
+        .unwrap_or_else(|e| {
+            log::error!("failed to record what a rule did: {e:#}"; path = from.display());
+            false
+        });
+        if changed {
+            signal_waybar(self.cfg.signal_number);
+        }
+    }
 
-            if !path.is_file() {
+    /// Record whatever the rules thread has finished with, and launch the
+    /// commands of the rules that asked for one.
+    fn collect_filed(&mut self) {
+        let mut entries: Vec<FileState> = Vec::new();
+        while let Ok(filed) = self.filed.try_recv() {
+            self.filing -= 1;
+            if let Some(argv) = &filed.run {
+                runner::launch(Command::new(&argv[0]).args(&argv[1..]));
+            }
+            if filed.aged {
+                self.refile(filed);
                 continue;
             }
-            let path_str = path.to_string_lossy().into_owned();
-            if seen.contains(&path_str) {
+            let Some(path) = filed.path.filter(|_| filed.notify) else {
                 continue;
+            };
+            if let Ok(mut st) = FileState::new(path, &*self.clock) {
+                st.tags = filed.tags;
+                entries.push(st);
             }
+        }
+        self.record(entries);
+    }
 
-            seen.insert(path_str);
+    /// Push new entries to history, oldest first, with a single write. A new
+    /// entry signals waybar at once; files that only joined a batch are
+    /// signalled once, when its window closes.
+    fn record(&mut self, entries: Vec<FileState>) {
+        if entries.is_empty() {
+            return;
+        }
 
-            if let Ok(st) = FileState::new(path.clone()) {
-                let state_file = Config::state_file();
-                let history_size = cfg.history_size;
-                let _ = with_history(&state_file, |history| {
-                    history.push(st, history_size);
-                });
-                signal_waybar(cfg.signal_number);
-                dismiss_at = Some(now_secs() + cfg.dismiss_seconds);
-                eprintln!("new: {}", path.display());
+        let cfg = &self.cfg;
+        let history_size = cfg.history_size;
+        let window = cfg.batch_window_ms as f64 / 1000.0;
+        let mut added = false;
+        let mut batch_ends = None;
+        if let Err(e) = with_history(&cfg.state_file(), |history| {
+            for st in &entries {
+                if !history.push_grouped(st.clone(), window, history_size) {
+                    added = true;
+                }
             }
+            batch_ends = history.entries.first().map(|top| top.time + window);
+        }) {
+            log::error!("failed to save history: {e:#}"; files = entries.len());
+            added = true;
+        }
+        if added {
+            self.pending_signal_at = None;
+            signal_waybar(cfg.signal_number);
+        } else {
+            self.pending_signal_at = self.pending_signal_at.or(batch_ends);
+        }
+        self.dismiss_at = Some(self.clock.now() + cfg.dismiss_seconds as f64);
+        for st in &entries {
+            log::info!("new"; path = st.path.display(), size = st.size);
+        }
+        if let Some(st) = entries.last() {
+            systemd::notify(&format!("STATUS=Last file: {}", st.name));
+        }
+    }
+}
+
+/// Paths glance is about to put files at, with when each stops being expected.
+type Expected = Vec<(PathBuf, f64)>;
+
+fn update_expected(file: &Path, f: impl FnOnce(&mut Expected)) -> Result<()> {
+    let _lock = FileLock::acquire(file)?;
+    let mut list: Expected = std::fs::read_to_string(file)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+    f(&mut list);
+    if list.is_empty() {
+        remove_stale(file);
+    } else {
+        std::fs::write(file, serde_json::to_string(&list)?)?;
+    }
+    Ok(())
+}
+
+/// Tell the watcher that glance itself is about to put files at `paths` (a
+/// rename, a move or an undone trash), so it follows them in history instead
+/// of announcing them as new.
+pub fn expect_arrivals(cfg: &Config, paths: &[PathBuf]) {
+    let now = SystemClock.now();
+    let file = cfg.expected_file();
+    let updated = update_expected(&file, |list| {
+        list.retain(|(p, until)| *until > now && !paths.contains(p));
+        list.extend(paths.iter().map(|p| (p.clone(), now + EXPECT_TTL)));
+    });
+    if let Err(e) = updated {
+        log::warn!("failed to record expected arrivals: {e:#}"; path = file.display());
+    }
+}
+
+/// Start the thread that carries out rule actions, one job at a time. It
+/// exits once the watcher drops its end.
+fn spawn_rules_thread() -> Result<(Sender<Job>, Receiver<Filed>)> {
+    let (jobs, queue) = mpsc::channel::<Job>();
+    let (done, filed) = mpsc::channel();
+    std::thread::Builder::new().name("glance-rules".into()).spawn(move || {
+        for job in queue {
+            if done.send(job.execute()).is_err() {
+                break;
+            }
+        }
+    })?;
+    Ok((jobs, filed))
+}
+
+/// A fresh inotify instance watching every configured dir that exists.
+fn add_watches(dirs: &[String]) -> Result<(Inotify, HashMap<i32, PathBuf>)> {
+    let inotify = Inotify::init()?;
+    let mut wd_to_dir: HashMap<i32, PathBuf> = HashMap::new();
+    for dir in dirs {
+        let path = PathBuf::from(shellexpand::tilde(dir).as_ref());
+        if path.is_dir() {
+            let wd = inotify.watches().add(
+                &path,
+                WatchMask::CLOSE_WRITE
+                    | WatchMask::MOVED_TO
+                    | WatchMask::MOVED_FROM
+                    | WatchMask::DELETE,
+            )?;
+            wd_to_dir.insert(wd.get_watch_descriptor_id(), path.clone());
+            log::info!("watching"; dir = path.display());
+        } else {
+            log::warn!("not watching: not a directory"; dir = path.display());
+        }
+    }
+    Ok((inotify, wd_to_dir))
+}
+
+fn mtime_secs(path: &Path) -> Option<f64> {
+    let meta = std::fs::metadata(path).ok()?;
+    if !meta.is_file() {
+        return None;
+    }
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(mtime.as_secs_f64())
+}
+
+pub fn run(cfg: &Config, replace: bool) -> Result<()> {
+    // held until exit; the pid file itself is left for the next instance
+    let _instance = PidLock::acquire(cfg, replace)?;
+
+    // cleanup on ctrl-c / SIGTERM
+    let sig_num = cfg.signal_number;
+    ctrlc::set_handler(move || {
+        signal_waybar(sig_num);
+        std::process::exit(0);
+    })?;
+
+    // SIGHUP re-reads the config (`systemctl --user reload glance`)
+    unsafe { libc::signal(libc::SIGHUP, on_sighup as *const () as libc::sighandler_t) };
+
+    let mut watcher = Watcher::new(cfg.clone(), Arc::new(SystemClock))?;
+    if cfg.startup_scan {
+        watcher.catch_up();
+    }
+    systemd::notify("READY=1");
+    systemd::notify(&format!("STATUS=Watching {} dir(s)", watcher.dir_count()));
+    let mut watchdog = Watchdog::from_env();
+    loop {
+        if let Some(w) = watchdog.as_mut() {
+            w.tick();
+        }
+        if RELOAD.swap(false, Ordering::SeqCst) {
+            reload(&mut watcher);
+        }
+        // 1s timeout so we can check the dismiss timer
+        watcher.step(Duration::from_secs(1))?;
+    }
+}
+
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sighup(_: libc::c_int) {
+    RELOAD.store(true, Ordering::SeqCst);
+}
+
+fn reload(watcher: &mut Watcher) {
+    systemd::reloading();
+    match Config::load() {
+        Ok(cfg) => match watcher.reload(cfg) {
+            Ok(()) => log::info!("config reloaded"),
+            Err(e) => log::error!("config reload failed: {e:#}"),
+        },
+        Err(e) => log::error!("config reload failed, keeping the old one: {e:#}"),
+    }
+    systemd::notify("READY=1");
+    systemd::notify(&format!("STATUS=Watching {} dir(s)", watcher.dir_count()));
+}
+
+/// `glance watch --status`: say whether a watcher is running, and return
+/// that so the exit code can tell scripts too.
+pub fn status(cfg: &Config) -> bool {
+    match running_pid(cfg) {
+        Some(pid) => {
+            println!("glance watch is running (pid {pid})");
+            true
+        }
+        None => {
+            println!("glance watch is not running");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seen_cache_evicts_after_ttl() {
+        let mut seen = SeenCache::new();
+        seen.insert("/a".into(), 0.0);
+        seen.insert("/b".into(), 10.0);
+        assert!(seen.contains("/a"));
+
+        seen.insert("/c".into(), SEEN_TTL + 5.0);
+        assert!(!seen.contains("/a"));
+        assert!(seen.contains("/b"));
+        assert!(seen.contains("/c"));
+    }
+
+    #[test]
+    fn seen_cache_drops_oldest_when_full() {
+        let mut seen = SeenCache::new();
+        for i in 0..MAX_SEEN {
+            seen.insert(format!("/{i}"), 1.0);
         }
+        seen.insert("/new".into(), 2.0);
+        assert!(!seen.contains("/0"));
+        assert!(seen.contains("/1"));
+        assert!(seen.contains("/new"));
+        assert_eq!(seen.entries.len(), MAX_SEEN);
     }
 }
@@ -0,0 +1,186 @@
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Output};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use wayglance::clock::{Clock, ManualClock, SystemClock};
+use wayglance::config::Config;
+use wayglance::runner::{self, CommandRunner};
+use wayglance::state::{read_history, HistoryState};
+use wayglance::status::format_status;
+use wayglance::watch::Watcher;
+
+/// Records every command instead of running it.
+#[derive(Default)]
+pub struct Recorder {
+    calls: RefCell<Vec<Vec<String>>>,
+    stdin: RefCell<Vec<Vec<u8>>>,
+    responses: RefCell<HashMap<String, Vec<u8>>>,
+}
+
+impl Recorder {
+    fn record(&self, cmd: &Command) {
+        let mut call = vec![cmd.get_program().to_string_lossy().into_owned()];
+        call.extend(cmd.get_args().map(|a| a.to_string_lossy().into_owned()));
+        self.calls.borrow_mut().push(call);
+    }
+
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.borrow().clone()
+    }
+
+    /// Calls whose program is `program`.
+    pub fn calls_to(&self, program: &str) -> Vec<Vec<String>> {
+        self.calls().into_iter().filter(|c| c[0] == program).collect()
+    }
+
+    /// What each call made with `output_with_stdin` was fed.
+    pub fn inputs(&self) -> Vec<Vec<u8>> {
+        self.stdin.borrow().clone()
+    }
+
+    /// Make `program` print `stdout` when run for its output.
+    pub fn respond(&self, program: &str, stdout: &[u8]) {
+        self.responses.borrow_mut().insert(program.into(), stdout.to_vec());
+    }
+
+    fn response(&self, cmd: &Command) -> Output {
+        let program = cmd.get_program().to_string_lossy();
+        Output {
+            status: ExitStatus::from_raw(0),
+            stdout: self.responses.borrow().get(&*program).cloned().unwrap_or_default(),
+            stderr: Vec::new(),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.calls.borrow_mut().clear();
+        self.stdin.borrow_mut().clear();
+    }
+}
+
+impl CommandRunner for Recorder {
+    fn spawn(&self, cmd: &mut Command) -> io::Result<()> {
+        self.record(cmd);
+        Ok(())
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        self.record(cmd);
+        Ok(self.response(cmd))
+    }
+
+    fn output_with_stdin(&self, cmd: &mut Command, input: &[u8]) -> io::Result<Output> {
+        self.record(cmd);
+        self.stdin.borrow_mut().push(input.to_vec());
+        Ok(self.response(cmd))
+    }
+
+    fn run_for(&self, cmd: &mut Command, _limit: Duration) -> io::Result<bool> {
+        self.record(cmd);
+        Ok(false)
+    }
+
+    /// Everything is installed.
+    fn installed(&self, _bin: &str) -> bool {
+        true
+    }
+}
+
+/// A watcher running against temp watch and runtime dirs, with a manual
+/// clock and recorded external commands.
+pub struct Harness {
+    pub watch_dir: PathBuf,
+    pub cfg: Config,
+    pub clock: Arc<ManualClock>,
+    pub recorder: Rc<Recorder>,
+    pub watcher: Watcher,
+    _tmp: TempDir,
+}
+
+/// Defaults for tests: grouping is off, since the manual clock would
+/// otherwise put every file in a test into one batch.
+pub fn config() -> Config {
+    Config { batch_window_ms: 0, ..Default::default() }
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_config(config())
+    }
+
+    pub fn with_config(mut cfg: Config) -> Self {
+        let tmp = tempfile::tempdir().unwrap();
+        let watch_dir = tmp.path().join("Screenshots");
+        let runtime_dir = tmp.path().join("runtime");
+        std::fs::create_dir_all(&watch_dir).unwrap();
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+        cfg.watch_dirs = vec![watch_dir.to_string_lossy().into_owned()];
+        cfg.runtime_dir = runtime_dir;
+        cfg.state_dir = tmp.path().join("state");
+        // never created, so nothing reaches for GLib to look up a thumbnail
+        cfg.cache_dir = tmp.path().join("cache");
+
+        let recorder = Rc::new(Recorder::default());
+        runner::set_runner(recorder.clone());
+
+        // start at the real time so commands that use the system clock agree
+        let clock = Arc::new(ManualClock::new(SystemClock.now()));
+        let watcher = Watcher::new(cfg.clone(), clock.clone()).unwrap();
+        Self {
+            watch_dir,
+            cfg,
+            clock,
+            recorder,
+            watcher,
+            _tmp: tmp,
+        }
+    }
+
+    /// Throw away the watcher (and any events it hadn't read) and start a
+    /// fresh one, as if the daemon had been restarted.
+    pub fn restart(&mut self) {
+        self.watcher = Watcher::new(self.cfg.clone(), self.clock.clone()).unwrap();
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.watch_dir.join(name)
+    }
+
+    pub fn write(&self, name: &str, contents: &[u8]) -> PathBuf {
+        let path = self.path(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Let the watcher drain whatever inotify has queued, and wait for the
+    /// rules thread to finish filing.
+    pub fn settle(&mut self) {
+        while self.watcher.step(Duration::from_millis(20)).unwrap() > 0 || self.watcher.filing() {}
+        // one more pass so timers are checked after the last event
+        self.watcher.step(Duration::ZERO).unwrap();
+    }
+
+    pub fn history(&self) -> HistoryState {
+        read_history(&self.cfg.state_file())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.history().entries.into_iter().map(|e| e.name).collect()
+    }
+
+    pub fn status(&self) -> serde_json::Value {
+        serde_json::from_str(&format_status(&self.cfg, None, &*self.clock)).unwrap()
+    }
+
+    pub fn waybar_signals(&self) -> usize {
+        self.recorder.calls_to("pkill").len()
+    }
+}
@@ -0,0 +1,588 @@
+mod common;
+
+use common::Harness;
+use wayglance::config::Config;
+use wayglance::select::Selector;
+
+#[test]
+fn new_file_is_pushed_and_signals_waybar() {
+    let mut h = Harness::new();
+    h.write("shot.png", b"png");
+    h.settle();
+
+    assert_eq!(h.names(), ["shot.png"]);
+    assert_eq!(h.history().entries[0].size, 3);
+    assert_eq!(
+        h.recorder.calls_to("pkill"),
+        [["pkill", "-RTMIN+8", "-x", "-o", "waybar"]]
+    );
+
+    let status = h.status();
+    assert_eq!(status["class"], "active");
+    assert_eq!(status["text"], " shot.png");
+}
+
+#[test]
+fn moved_in_files_are_picked_up() {
+    let mut h = Harness::new();
+    let outside = h.watch_dir.parent().unwrap().join("elsewhere.pdf");
+    std::fs::write(&outside, b"pdf").unwrap();
+    std::fs::rename(&outside, h.path("invoice.pdf")).unwrap();
+    h.settle();
+
+    assert_eq!(h.names(), ["invoice.pdf"]);
+}
+
+#[test]
+fn dotfiles_and_partial_downloads_are_ignored() {
+    let mut h = Harness::new();
+    h.write(".hidden", b"x");
+    h.write("movie.mkv.part", b"x");
+    h.settle();
+    assert!(h.names().is_empty());
+    assert_eq!(h.waybar_signals(), 0);
+
+    // the finished download is renamed into place
+    std::fs::rename(h.path("movie.mkv.part"), h.path("movie.mkv")).unwrap();
+    h.settle();
+    assert_eq!(h.names(), ["movie.mkv"]);
+}
+
+#[test]
+fn rewriting_a_seen_file_does_not_push_it_again() {
+    let mut h = Harness::new();
+    h.write("notes.txt", b"one");
+    h.settle();
+    h.write("notes.txt", b"two");
+    h.settle();
+
+    assert_eq!(h.names(), ["notes.txt"]);
+    assert_eq!(h.waybar_signals(), 1);
+}
+
+#[test]
+fn history_is_capped_at_history_size() {
+    let mut h = Harness::with_config(Config { history_size: 2, ..common::config() });
+    for name in ["a.png", "b.png", "c.png"] {
+        h.write(name, b"x");
+        h.settle();
+    }
+    assert_eq!(h.names(), ["c.png", "b.png"]);
+}
+
+#[test]
+fn dismiss_timer_hides_widget_and_signals_again() {
+    let mut h = Harness::new();
+    h.write("shot.png", b"png");
+    h.settle();
+    assert_eq!(h.waybar_signals(), 1);
+
+    h.clock.advance(h.cfg.dismiss_seconds as f64 - 1.0);
+    h.settle();
+    assert_eq!(h.waybar_signals(), 1);
+    assert_eq!(h.status()["class"], "active");
+
+    h.clock.advance(3.5);
+    h.settle();
+    assert_eq!(h.waybar_signals(), 2);
+    assert_eq!(h.status()["class"], "empty");
+}
+
+#[test]
+fn open_menu_holds_off_dismiss_signal() {
+    let mut h = Harness::new();
+    h.write("shot.png", b"png");
+    h.settle();
+    std::fs::write(h.cfg.menu_lock_file(), "").unwrap();
+
+    h.clock.advance(h.cfg.dismiss_seconds as f64 + 5.0);
+    h.settle();
+    assert_eq!(h.waybar_signals(), 1);
+}
+
+#[test]
+fn copy_hands_the_visible_path_to_wl_copy() {
+    let mut h = Harness::new();
+    let path = h.write("shot.png", b"png");
+    h.settle();
+    h.recorder.clear();
+
+    wayglance::copy::run(&h.cfg, &Selector::default()).unwrap();
+    assert_eq!(
+        h.recorder.calls(),
+        [vec!["wl-copy".to_string(), path.to_string_lossy().into_owned()]]
+    );
+}
+
+#[test]
+fn renaming_an_announced_file_updates_its_entry() {
+    let mut h = Harness::new();
+    h.write("Screenshot_001.png", b"png");
+    h.settle();
+    std::fs::rename(h.path("Screenshot_001.png"), h.path("diagram.png")).unwrap();
+    h.settle();
+
+    let history = h.history();
+    assert_eq!(h.names(), ["diagram.png"]);
+    assert_eq!(history.entries[0].path, h.path("diagram.png"));
+    assert!(!history.entries[0].missing);
+}
+
+#[test]
+fn deleting_an_announced_file_marks_it_missing() {
+    let mut h = Harness::new();
+    h.write("a.png", b"x");
+    h.settle();
+    h.write("b.png", b"x");
+    h.settle();
+    std::fs::remove_file(h.path("a.png")).unwrap();
+    h.settle();
+
+    let history = h.history();
+    assert_eq!(h.names(), ["b.png", "a.png"]);
+    assert!(!history.entries[0].missing);
+    assert!(history.entries[1].missing);
+    let tooltip = h.status()["tooltip"].as_str().unwrap().to_string();
+    assert!(tooltip.contains("<span fgalpha=\"50%\">  a.png (1 B) — missing</span>"));
+
+    // the same name showing up again is a new file
+    h.write("a.png", b"again");
+    h.settle();
+    assert_eq!(h.names(), ["a.png", "b.png", "a.png"]);
+    assert!(!h.history().entries[0].missing);
+}
+
+#[test]
+fn moving_a_file_out_of_watched_dirs_marks_it_missing() {
+    let mut h = Harness::new();
+    h.write("shot.png", b"png");
+    h.settle();
+    let outside = h.watch_dir.parent().unwrap().join("shot.png");
+    std::fs::rename(h.path("shot.png"), &outside).unwrap();
+    h.settle();
+    assert!(!h.history().entries[0].missing);
+
+    // the MOVED_FROM is only given up on once its pairing window passes
+    h.clock.advance(2.0);
+    h.settle();
+    assert!(h.history().entries[0].missing);
+}
+
+#[test]
+fn queue_overflow_is_recovered_by_rescanning() {
+    let limit: usize = std::fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(16384);
+    if limit > 100_000 {
+        eprintln!("max_queued_events is {limit}, too large to flood in a test");
+        return;
+    }
+
+    let mut h = Harness::new();
+    // nobody is reading while we write, so the kernel queue overflows
+    let total = limit + 500;
+    for i in 0..total {
+        h.write(&format!("file-{i:06}.txt"), b"x");
+    }
+    h.settle();
+
+    let expected: Vec<String> = (total - 5..total).rev().map(|i| format!("file-{i:06}.txt")).collect();
+    assert_eq!(h.names(), expected);
+    // one signal per read plus one for the rescan, not one per file
+    assert!(h.waybar_signals() < 100, "{} signals", h.waybar_signals());
+}
+
+#[test]
+fn startup_scan_catches_files_from_while_the_watcher_was_down() {
+    let mut h = Harness::with_config(Config { startup_scan: true, ..common::config() });
+    h.write("a.png", b"x");
+    h.settle();
+    h.recorder.clear();
+
+    // written while "down": the old watcher never reads these events
+    h.write("b.png", b"x");
+    h.write("c.png", b"x");
+    h.restart();
+    h.watcher.catch_up();
+
+    assert_eq!(h.names(), ["c.png", "b.png", "a.png"]);
+    assert_eq!(h.waybar_signals(), 1);
+
+    // their events, if any are still to come, don't announce them twice
+    h.settle();
+    assert_eq!(h.names(), ["c.png", "b.png", "a.png"]);
+}
+
+#[test]
+fn startup_scan_ignores_files_older_than_its_window() {
+    let mut h = Harness::with_config(Config {
+        startup_scan: true,
+        startup_scan_seconds: 60,
+        ..common::config()
+    });
+    let old = h.write("old.png", b"x");
+    let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    std::fs::File::options()
+        .write(true)
+        .open(&old)
+        .unwrap()
+        .set_modified(an_hour_ago)
+        .unwrap();
+    h.write("new.png", b"x");
+    h.write(".hidden.png", b"x");
+
+    h.restart();
+    h.watcher.catch_up();
+    assert_eq!(h.names(), ["new.png"]);
+}
+
+#[test]
+fn burst_of_files_becomes_one_batch_entry() {
+    let mut h = Harness::with_config(Config { batch_window_ms: 1500, ..common::config() });
+    h.write("older.png", b"x");
+    h.settle();
+    h.clock.advance(60.0);
+    h.settle();
+
+    h.recorder.clear();
+    // waybar hears about the first file at once, and about the rest of the
+    // batch once its window closes
+    h.write("page-00.jpg", b"jpeg");
+    h.settle();
+    assert_eq!(h.waybar_signals(), 1);
+    for i in 1..12 {
+        h.write(&format!("page-{i:02}.jpg"), b"jpeg");
+    }
+    h.settle();
+    assert_eq!(h.waybar_signals(), 1);
+    h.clock.advance(2.0);
+    h.settle();
+    assert_eq!(h.waybar_signals(), 2);
+
+    let history = h.history();
+    assert_eq!(h.names(), ["12 files in Screenshots", "older.png"]);
+    assert_eq!(history.entries[0].files.len(), 12);
+    assert_eq!(history.entries[0].size, 48);
+    assert_eq!(h.status()["text"], " 12 files in Scr\u{2026} (1/2)");
+
+    // copying a batch hands over every path, one per line
+    h.recorder.clear();
+    wayglance::copy::run(&h.cfg, &Selector::default()).unwrap();
+    let calls = h.recorder.calls_to("wl-copy");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0][1].lines().count(), 12);
+    assert!(calls[0][1].starts_with(&*h.path("page-00.jpg").to_string_lossy()));
+
+    // the window runs from the batch's first file, so a late one starts anew
+    h.write("late.jpg", b"jpeg");
+    h.settle();
+    assert_eq!(h.names()[..2], ["late.jpg", "12 files in Screenshots"]);
+}
+
+#[test]
+fn reload_switches_watch_dirs_and_catches_the_gap() {
+    let mut h = Harness::new();
+    let downloads = h.watch_dir.parent().unwrap().join("Downloads");
+    std::fs::create_dir_all(&downloads).unwrap();
+    // lands before the reload, while nobody watches the dir yet
+    std::fs::write(downloads.join("early.zip"), b"zip").unwrap();
+
+    let cfg = Config {
+        watch_dirs: vec![downloads.to_string_lossy().into_owned()],
+        ..h.cfg.clone()
+    };
+    h.watcher.reload(cfg).unwrap();
+    assert_eq!(h.watcher.dir_count(), 1);
+    h.write("old-dir.png", b"png");
+    std::fs::write(downloads.join("late.zip"), b"zip").unwrap();
+    h.settle();
+
+    assert_eq!(h.names(), ["late.zip", "early.zip"]);
+}
+
+#[test]
+fn copy_with_a_selector_leaves_the_selection_alone() {
+    let mut h = Harness::new();
+    let older = h.write("older.png", b"png");
+    h.clock.advance(1.0);
+    h.write("newer.png", b"png");
+    h.settle();
+    h.recorder.clear();
+
+    let by_name = Selector { name: Some("old*".into()), ..Default::default() };
+    wayglance::copy::run(&h.cfg, &by_name).unwrap();
+    wayglance::copy::run(&h.cfg, &Selector::index(1)).unwrap();
+    let copied: Vec<String> = h.recorder.calls_to("wl-copy").into_iter().map(|c| c[1].clone()).collect();
+    let older = older.to_string_lossy().into_owned();
+    assert_eq!(copied, [older.clone(), older]);
+    assert_eq!(h.history().selected, 0);
+
+    assert!(wayglance::copy::run(&h.cfg, &Selector::index(5)).is_err());
+}
+
+#[test]
+fn external_drag_tools_are_handed_the_entry_files() {
+    let mut h = Harness::new();
+    let shot = h.write("shot.png", b"png");
+    h.settle();
+    h.recorder.clear();
+
+    h.cfg.drag_command = "ripdrag -x".into();
+    wayglance::drag::run(&h.cfg, false, &[], &Selector::default()).unwrap();
+    let shot = shot.to_string_lossy().into_owned();
+    assert_eq!(h.recorder.calls_to("ripdrag"), [["ripdrag", "-x", shot.as_str()]]);
+}
+
+#[test]
+fn dragging_by_indices_needs_every_index_to_exist() {
+    let mut h = Harness::new();
+    let older = h.write("older.png", b"png");
+    h.clock.advance(1.0);
+    let newer = h.write("newer.png", b"png");
+    h.settle();
+    h.recorder.clear();
+
+    h.cfg.drag_command = "ripdrag".into();
+    assert!(wayglance::drag::run(&h.cfg, false, &[0, 99], &Selector::default()).is_err());
+    assert!(h.recorder.calls_to("ripdrag").is_empty());
+
+    wayglance::drag::run(&h.cfg, false, &[1, 0], &Selector::default()).unwrap();
+    let (older, newer) = (older.to_string_lossy(), newer.to_string_lossy());
+    assert_eq!(h.recorder.calls_to("ripdrag"), [["ripdrag", &*older, &*newer]]);
+}
+
+#[test]
+fn pick_feeds_the_launcher_and_acts_on_the_choice() {
+    let mut h = Harness::new();
+    let first = h.write("first.txt", b"1");
+    h.clock.advance(1.0);
+    h.write("second.txt", b"2");
+    h.settle();
+    h.recorder.clear();
+    h.recorder.respond("rofi", b"1  first.txt  (1 B, 1s ago)\n");
+
+    wayglance::pick::run(&h.cfg, wayglance::pick::Action::Copy, None).unwrap();
+
+    // everything is installed under the recorder, so auto picks rofi
+    let rofi = &h.recorder.calls_to("rofi")[0];
+    assert_eq!(rofi[1..], ["-dmenu", "-i", "-p", "glance", "-show-icons"]);
+    let fed = String::from_utf8(h.recorder.inputs()[0].clone()).unwrap();
+    assert!(fed.starts_with("0  second.txt  (1 B, "));
+    assert!(fed.contains("\0icon\x1ftext-x-generic\n1  first.txt"));
+
+    let copied = h.recorder.calls_to("wl-copy");
+    assert_eq!(copied, [vec!["wl-copy".to_string(), first.to_string_lossy().into_owned()]]);
+    assert_eq!(h.history().selected, 0);
+}
+
+#[test]
+fn renaming_from_glance_follows_the_entry_without_announcing_it() {
+    let mut h = Harness::new();
+    h.write("Screenshot_001.png", b"png");
+    h.clock.advance(1.0);
+    h.settle();
+    h.recorder.clear();
+
+    wayglance::rename::run(&h.cfg, "diagram.png", &Selector::default()).unwrap();
+    h.settle();
+
+    assert_eq!(h.names(), ["diagram.png"]);
+    assert_eq!(h.history().entries[0].path, h.path("diagram.png"));
+    assert!(h.path("diagram.png").exists());
+    // one signal for the rename itself, none for a "new" file
+    assert_eq!(h.waybar_signals(), 1);
+
+    h.write("other.png", b"x");
+    assert!(wayglance::rename::run(&h.cfg, "other.png", &Selector::default()).is_err());
+    assert!(wayglance::rename::run(&h.cfg, "../up.png", &Selector::default()).is_err());
+}
+
+#[test]
+fn deleting_from_glance_drops_the_entry() {
+    let mut h = Harness::new();
+    h.write("a.png", b"x");
+    h.clock.advance(1.0);
+    h.settle();
+    h.write("b.png", b"x");
+    h.clock.advance(1.0);
+    h.settle();
+
+    wayglance::delete::run(&h.cfg, &Selector::index(1), true).unwrap();
+    h.settle();
+
+    assert!(!h.path("a.png").exists());
+    assert_eq!(h.names(), ["b.png"]);
+    assert_eq!(h.history().selected, 0);
+}
+
+#[test]
+fn moving_from_glance_follows_the_entry_and_keeps_both_names() {
+    let mut h = Harness::new();
+    h.write("invoice.pdf", b"pdf");
+    h.clock.advance(1.0);
+    h.settle();
+    let dest = h.watch_dir.parent().unwrap().join("invoices");
+    std::fs::create_dir(&dest).unwrap();
+    std::fs::write(dest.join("invoice.pdf"), b"older").unwrap();
+
+    wayglance::move_to::run(&h.cfg, &dest, &Selector::default()).unwrap();
+    h.clock.advance(2.0);
+    h.settle();
+
+    let dest = dest.canonicalize().unwrap();
+    let entry = &h.history().entries[0];
+    assert_eq!(entry.path, dest.join("invoice (2).pdf"));
+    assert!(!entry.missing);
+    assert_eq!(std::fs::read(&entry.path).unwrap(), b"pdf");
+    assert_eq!(std::fs::read(dest.join("invoice.pdf")).unwrap(), b"older");
+    assert!(wayglance::move_to::run(&h.cfg, &dest.join("nope"), &Selector::default()).is_err());
+    assert_eq!(wayglance::move_to::destinations(&h.cfg), [dest]);
+
+    // moving it back in is a move, not a new download
+    wayglance::move_to::run(&h.cfg, &h.watch_dir, &Selector::default()).unwrap();
+    h.settle();
+    assert_eq!(h.names(), ["invoice (2).pdf"]);
+
+    // the same dir by another name is still where it already is
+    let link = h.watch_dir.parent().unwrap().join("shots");
+    std::os::unix::fs::symlink(&h.watch_dir, &link).unwrap();
+    assert!(wayglance::move_to::run(&h.cfg, &link, &Selector::default()).is_err());
+    assert!(h.path("invoice (2).pdf").exists());
+}
+
+#[test]
+fn copying_from_glance_leaves_the_entry_alone() {
+    let mut h = Harness::new();
+    let shot = h.write("shot.png", b"png");
+    h.clock.advance(1.0);
+    h.settle();
+    let dest = h.watch_dir.parent().unwrap().join("keep");
+    std::fs::create_dir(&dest).unwrap();
+
+    wayglance::move_to::run_copy(&h.cfg, &dest, &Selector::default()).unwrap();
+    wayglance::move_to::run_copy(&h.cfg, &dest, &Selector::default()).unwrap();
+    // a copy into a watched dir isn't news either
+    wayglance::move_to::run_copy(&h.cfg, &h.watch_dir, &Selector::default()).unwrap();
+    h.settle();
+
+    let dest = dest.canonicalize().unwrap();
+    assert_eq!(std::fs::read(dest.join("shot.png")).unwrap(), b"png");
+    assert!(dest.join("shot (2).png").exists());
+    assert!(h.path("shot (2).png").exists());
+    assert_eq!(h.history().entries[0].path, shot);
+    assert_eq!(h.names(), ["shot.png"]);
+}
+
+#[test]
+fn only_arrivals_glance_expected_are_left_unannounced() {
+    let mut h = Harness::new();
+    let outside = h.watch_dir.parent().unwrap().join("restored.png");
+    std::fs::write(&outside, b"png").unwrap();
+    wayglance::watch::expect_arrivals(&h.cfg, &[h.path("restored.png")]);
+
+    std::fs::rename(&outside, h.path("restored.png")).unwrap();
+    h.settle();
+    assert!(h.names().is_empty());
+    assert!(!h.cfg.expected_file().exists());
+
+    // the expectation is used up: the same move again is news
+    std::fs::rename(h.path("restored.png"), &outside).unwrap();
+    h.clock.advance(2.0);
+    h.settle();
+    std::fs::rename(&outside, h.path("restored.png")).unwrap();
+    h.settle();
+    assert_eq!(h.names(), ["restored.png"]);
+}
+
+#[test]
+fn rules_file_new_arrivals_before_they_are_announced() {
+    let mut h = Harness::new();
+    let invoices = h.watch_dir.parent().unwrap().join("invoices");
+    let rules = format!(
+        r#"
+        [[rules]]
+        name = "invoices"
+        glob = "invoice*.pdf"
+        move = "{}"
+        run = "notify-send Filed"
+        tags = ["finance"]
+
+        [[rules]]
+        glob = "scan*"
+        rename = "{{stem}}-archived.{{ext}}"
+        notify = false
+        "#,
+        invoices.display()
+    );
+    h.cfg.rules = toml::from_str::<wayglance::config::Config>(&rules).unwrap().rules;
+    h.restart();
+
+    h.write("invoice-42.pdf", b"pdf");
+    h.clock.advance(1.0);
+    h.settle();
+    let filed = invoices.join("invoice-42.pdf");
+    assert!(filed.exists());
+    let history = h.history();
+    assert_eq!(history.entries[0].path, filed);
+    assert_eq!(history.entries[0].tags, ["finance"]);
+    let ran = h.recorder.calls_to("notify-send");
+    assert_eq!(ran, [["notify-send", "Filed", filed.to_str().unwrap()]]);
+
+    // quietly renamed, and the rename isn't taken for a new file either
+    h.write("scan.png", b"png");
+    h.clock.advance(1.0);
+    h.settle();
+    assert!(h.path("scan-archived.png").exists());
+    assert_eq!(h.names(), ["invoice-42.pdf"]);
+
+    h.write("other.png", b"png");
+    h.settle();
+    assert_eq!(h.names(), ["other.png", "invoice-42.pdf"]);
+    assert!(h.history().entries[0].tags.is_empty());
+}
+
+#[test]
+fn age_rules_act_on_history_once_files_are_old_enough() {
+    let mut h = Harness::new();
+    let archive = h.watch_dir.parent().unwrap().join("archive");
+    let rules = format!(
+        r#"
+        [[rules]]
+        glob = "*.pdf"
+        min_age_days = 30
+        move = "{0}"
+
+        [[rules]]
+        min_age_days = 30
+        copy = "{0}"
+        tags = ["old"]
+        "#,
+        archive.display()
+    );
+    h.cfg.rules = toml::from_str::<wayglance::config::Config>(&rules).unwrap().rules;
+    h.restart();
+
+    h.write("shot.png", b"png");
+    h.write("doc.pdf", b"pdf");
+    h.clock.advance(1.0);
+    h.settle();
+    // too young to match when they arrive
+    assert_eq!(h.names(), ["doc.pdf", "shot.png"]);
+    assert!(!archive.exists());
+
+    h.clock.advance(31.0 * 86400.0);
+    h.settle();
+    let history = h.history();
+    assert_eq!(history.entries[0].path, archive.join("doc.pdf"));
+    assert!(!h.path("doc.pdf").exists());
+    assert!(archive.join("shot.png").exists());
+    assert_eq!(history.entries[1].tags, ["old"]);
+    assert!(history.entries.iter().all(|e| e.swept));
+
+    // swept once: the next sweep doesn't copy it again
+    h.clock.advance(3601.0);
+    h.settle();
+    assert_eq!(std::fs::read_dir(&archive).unwrap().count(), 2);
+    assert_eq!(h.names(), ["doc.pdf", "shot.png"]);
+}